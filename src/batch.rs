@@ -0,0 +1,108 @@
+//! Combine several [`Svg`]s into a single mesh.
+
+use bevy::{math::Vec2, prelude::Transform, render::mesh::Mesh};
+use lyon_tessellation::{FillTessellator, StrokeTessellator};
+
+use crate::{Convert, svg::{Origin, Svg}, tessellation, vertex_buffer::{BufferExt, VertexBuffers}};
+
+
+/// Tessellates several [`Svg`]s into one [`Mesh`], so that all of them are drawn with a single
+/// draw call. Useful for rows of small icons, like in menus.
+pub struct SvgBatch {
+    buffers: VertexBuffers,
+    fill_tess: FillTessellator,
+    stroke_tess: StrokeTessellator,
+}
+
+impl SvgBatch {
+    /// Create a new and empty [`SvgBatch`].
+    pub fn new() -> Self {
+        Self {
+            buffers: VertexBuffers::new(),
+            fill_tess: FillTessellator::new(),
+            stroke_tess: StrokeTessellator::new(),
+        }
+    }
+
+    /// Tessellates the given [`Svg`] and adds it to the batch, placed with `transform`.
+    /// The top left corner of the [`Svg`] is placed at the translation of `transform`.
+    pub fn add(&mut self, svg: &Svg, transform: Transform) -> &mut Self {
+        let mut buffer = tessellation::generate_buffer(svg, Origin::TopLeft, &mut self.fill_tess, &mut self.stroke_tess);
+        buffer.apply_transform(transform);
+        self.buffers.extend_one(buffer);
+        self
+    }
+
+    /// Adds `cols` × `rows` copies of the given [`Svg`] in a grid, with `spacing` between them.
+    ///
+    /// The grid starts at `(0, 0)` with the top left corner of the first copy and grows to the
+    /// right and downwards. The [`Svg`] is only tessellated once.
+    pub fn tile(&mut self, svg: &Svg, cols: u32, rows: u32, spacing: Vec2) -> &mut Self {
+        let buffer = tessellation::generate_buffer(svg, Origin::TopLeft, &mut self.fill_tess, &mut self.stroke_tess);
+        let step = Vec2::new(svg.view_box.w as f32, svg.view_box.h as f32) + spacing;
+
+        for row in 0..rows {
+            for col in 0..cols {
+                let mut copy = buffer.clone();
+                copy.apply_transform(Transform::from_xyz(col as f32 * step.x, -(row as f32) * step.y, 0.0));
+                self.buffers.extend_one(copy);
+            }
+        }
+        self
+    }
+
+    /// Build a single [`Mesh`] out of all [`Svg`]s added to this batch.
+    pub fn build(self) -> Mesh {
+        self.buffers.convert()
+    }
+}
+
+impl Default for SvgBatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{svg::load_test_svg, vertex_buffer::MeshData};
+
+    fn rect() -> Svg {
+        load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <rect width="10" height="10" fill="red"/>
+        </svg>"#)
+    }
+
+    fn single(svg: &Svg) -> VertexBuffers {
+        tessellation::generate_buffer(svg, Origin::TopLeft, &mut FillTessellator::new(), &mut StrokeTessellator::new())
+    }
+
+    #[test]
+    fn add_merges_svgs_into_one_mesh() {
+        let svg = rect();
+        let mut batch = SvgBatch::new();
+        batch.add(&svg, Transform::identity())
+            .add(&svg, Transform::from_xyz(20.0, 0.0, 0.0));
+        let mesh = batch.build();
+
+        let single = single(&svg);
+        assert_eq!(mesh.count_vertices(), 2 * single.vertices.len());
+        assert_eq!(mesh.indices().unwrap().len(), 2 * single.indices.len());
+    }
+
+    #[test]
+    fn tile_places_one_copy_per_grid_cell() {
+        let svg = rect();
+        let mut batch = SvgBatch::new();
+        batch.tile(&svg, 2, 2, Vec2::new(5.0, 5.0));
+        let mesh: MeshData = batch.buffers.clone().convert();
+
+        let single = single(&svg).vertices.len();
+        assert_eq!(mesh.positions.len(), 4 * single);
+        let corners: Vec<[f32; 2]> = mesh.positions.chunks(single)
+            .map(|copy| copy.iter().fold([f32::INFINITY, f32::NEG_INFINITY], |[x, y], p| [x.min(p[0]), y.max(p[1])]))
+            .collect();
+        assert_eq!(corners, vec![[0.0, 0.0], [15.0, 0.0], [0.0, -15.0], [15.0, -15.0]]);
+    }
+}
@@ -1,6 +1,6 @@
 //! Bevy [`Bundle`] representing an SVG entity.
 
-use crate::{plugin::SVG_PIPELINE_HANDLE, svg::Svg, prelude::Origin};
+use crate::{plugin::{SvgPathRanges, SvgState, SVG_PIPELINE_HANDLE}, svg::Svg, prelude::Origin};
 use bevy::{
     asset::Handle, ecs::bundle::Bundle,
     render::{
@@ -18,6 +18,10 @@ pub struct SvgBundle {
     pub svg: Handle<Svg>,
     /// Origin of the coordinate system and as such the origin for the Bevy position.
     pub origin: Origin,
+    /// Progress of building the mesh, updated by the plugin.
+    pub state: SvgState,
+    /// Vertices of every path in the mesh, updated by the plugin.
+    pub path_ranges: SvgPathRanges,
     pub mesh: Handle<Mesh>,
     pub main_pass: MainPass,
     pub draw: Draw,
@@ -33,6 +37,8 @@ impl Default for SvgBundle {
         Self {
             svg: Default::default(),
             origin: Default::default(),
+            state: Default::default(),
+            path_ranges: Default::default(),
             mesh: Default::default(),
             render_pipelines: RenderPipelines::from_pipelines(vec![RenderPipeline::new(
                 SVG_PIPELINE_HANDLE.typed(),
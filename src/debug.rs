@@ -0,0 +1,167 @@
+//! Human readable output of an [`Svg`], to debug what was actually loaded.
+
+use std::fmt::Write;
+
+use lyon_svg::path::PathEvent;
+
+use crate::svg::{DrawType, Svg};
+
+
+impl Svg {
+    /// A human readable summary of this [`Svg`], one line per path with its id, draw type,
+    /// color, bounds and number of segments. Meant for debugging, the format may change.
+    pub fn debug_summary(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{}: {}x{}, {} paths",
+            self.name, self.width, self.height, self.paths.len()
+        );
+
+        for (i, path) in self.paths.iter().enumerate() {
+            let draw_type = match path.draw_type {
+                DrawType::Fill(_) => "fill",
+                DrawType::Stroke(_) => "stroke",
+            };
+            let bounds = match path.bounds() {
+                Some((min, max)) => format!("({}, {})..({}, {})", min.x, min.y, max.x, max.y),
+                None => "empty".to_string(),
+            };
+            let _ = writeln!(
+                out,
+                "  [{}] id: {:?}, {}, color: {:?}, bounds: {}, segments: {}",
+                i, path.id, draw_type, path.color, bounds, path.segments.len()
+            );
+        }
+
+        out
+    }
+
+    /// Serializes the paths of this [`Svg`] into a minimal SVG document.
+    ///
+    /// The output isn't byte-identical to the source file, but contains the same geometry,
+    /// fills and strokes as parsed by this crate. Useful to debug what was actually loaded.
+    pub fn to_svg_string(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+            self.width, self.height, self.view_box.x, self.view_box.y, self.view_box.w, self.view_box.h
+        );
+        // The geometry is relative to the top left corner of the viewbox.
+        let _ = writeln!(out, r#"  <g transform="translate({} {})">"#, self.view_box.x, self.view_box.y);
+
+        for path in &self.paths {
+            let mut data = String::new();
+            for event in &path.segments {
+                let _ = match *event {
+                    PathEvent::Begin { at } => write!(data, "M {} {} ", at.x, at.y),
+                    PathEvent::Line { to, .. } => write!(data, "L {} {} ", to.x, to.y),
+                    PathEvent::Quadratic { ctrl, to, .. } =>
+                        write!(data, "Q {} {} {} {} ", ctrl.x, ctrl.y, to.x, to.y),
+                    PathEvent::Cubic { ctrl1, ctrl2, to, .. } =>
+                        write!(data, "C {} {} {} {} {} {} ", ctrl1.x, ctrl1.y, ctrl2.x, ctrl2.y, to.x, to.y),
+                    PathEvent::End { close: true, .. } => write!(data, "Z "),
+                    PathEvent::End { close: false, .. } => Ok(()),
+                };
+            }
+
+            let color = format!(
+                "#{:02x}{:02x}{:02x}",
+                (path.color.r() * 255.0).round() as u8,
+                (path.color.g() * 255.0).round() as u8,
+                (path.color.b() * 255.0).round() as u8
+            );
+            let paint = match path.draw_type {
+                DrawType::Fill(opts) => format!(
+                    r#"fill="{}" fill-opacity="{}" fill-rule="{}" stroke="none""#,
+                    color,
+                    path.color.a(),
+                    match opts.fill_rule {
+                        lyon_tessellation::FillRule::EvenOdd => "evenodd",
+                        lyon_tessellation::FillRule::NonZero => "nonzero",
+                    }
+                ),
+                DrawType::Stroke(opts) => format!(
+                    r#"fill="none" stroke="{}" stroke-opacity="{}" stroke-width="{}" stroke-linecap="{}" stroke-linejoin="{}""#,
+                    color,
+                    path.color.a(),
+                    opts.line_width,
+                    match opts.start_cap {
+                        lyon_tessellation::LineCap::Butt => "butt",
+                        lyon_tessellation::LineCap::Square => "square",
+                        lyon_tessellation::LineCap::Round => "round",
+                    },
+                    match opts.line_join {
+                        lyon_tessellation::LineJoin::Miter => "miter",
+                        lyon_tessellation::LineJoin::MiterClip => "miter-clip",
+                        lyon_tessellation::LineJoin::Round => "round",
+                        lyon_tessellation::LineJoin::Bevel => "bevel",
+                    }
+                ),
+            };
+
+            let m = path.abs_transform.compute_matrix();
+            let _ = writeln!(
+                out,
+                r#"    <path d="{}" transform="matrix({} {} {} {} {} {})" {}/>"#,
+                data.trim_end(),
+                m.x_axis.x, m.x_axis.y, m.y_axis.x, m.y_axis.y, m.w_axis.x, m.w_axis.y,
+                paint
+            );
+        }
+
+        out.push_str("  </g>\n</svg>\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Color;
+
+    use crate::svg::load_test_svg;
+
+    use super::*;
+
+    #[test]
+    fn to_svg_string_round_trips_the_geometry() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="10 20 100 50">
+            <g transform="translate(30 10) scale(2)">
+                <rect x="10" y="20" width="10" height="5" fill="red" stroke="blue"/>
+            </g>
+            <circle cx="80" cy="50" r="10" fill="green"/>
+        </svg>"#);
+        let reparsed = load_test_svg(&svg.to_svg_string());
+
+        assert_eq!(reparsed.paths.len(), svg.paths.len());
+        assert_eq!(
+            (reparsed.view_box.x, reparsed.view_box.y, reparsed.view_box.w, reparsed.view_box.h),
+            (svg.view_box.x, svg.view_box.y, svg.view_box.w, svg.view_box.h)
+        );
+        for (original, reparsed) in svg.paths.iter().zip(&reparsed.paths) {
+            let (min, max) = original.bounds().unwrap();
+            let (reparsed_min, reparsed_max) = reparsed.bounds().unwrap();
+            assert!(min.distance(reparsed_min) < 1e-3, "{} became {}", min, reparsed_min);
+            assert!(max.distance(reparsed_max) < 1e-3, "{} became {}", max, reparsed_max);
+            assert_eq!(
+                std::mem::discriminant(&original.draw_type),
+                std::mem::discriminant(&reparsed.draw_type)
+            );
+        }
+    }
+
+    #[test]
+    fn debug_summary_lists_every_path_with_its_color() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 10">
+            <rect id="left" width="10" height="10" fill="red"/>
+            <rect id="right" x="10" width="10" height="10" fill="blue"/>
+        </svg>"#);
+        let summary = svg.debug_summary();
+
+        assert!(summary.starts_with("test: "), "{}", summary);
+        assert_eq!(summary.lines().count(), 3);
+        assert!(summary.contains(&format!(r#"id: "left", fill, color: {:?}"#, Color::rgb_u8(255, 0, 0))), "{}", summary);
+        assert!(summary.contains(&format!(r#"id: "right", fill, color: {:?}"#, Color::rgb_u8(0, 0, 255))), "{}", summary);
+    }
+}
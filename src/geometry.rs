@@ -0,0 +1,137 @@
+//! Queries on the outline geometry of an [`Svg`], e.g. for colliders or picking.
+
+use bevy::math::{Vec2, Vec3};
+use lyon_geom::{CubicBezierSegment, QuadraticBezierSegment};
+use lyon_svg::path::PathEvent;
+use lyon_tessellation::math::Point;
+
+use crate::svg::{PathDescriptor, Svg};
+
+
+impl Svg {
+    /// The segments of every path with its `abs_transform` applied, in the same order as
+    /// [`Svg::paths`], relative to the top left corner of the viewbox.
+    /// See [`PathDescriptor::world_segments`].
+    pub fn path_events_world(&self) -> Vec<Vec<PathEvent>> {
+        self.paths.iter().map(PathDescriptor::world_segments).collect()
+    }
+
+    /// Flattens the outlines of all paths into polylines, one per subpath, e.g. to build
+    /// 2D colliders for physics or picking. Curves are approximated within `tolerance`.
+    ///
+    /// Points are relative to the top left corner of the viewbox, like [`Svg::path_events_world`],
+    /// and the polylines are implicitly closed, the first point isn't repeated at the end.
+    ///
+    /// A path with both a fill and a stroke yields its polylines once, from the first of them.
+    pub fn colliders(&self, tolerance: f32) -> Vec<Vec<Vec2>> {
+        let mut paths = self.paths.clone();
+        paths.dedup_by(|path, prev| path.same_outline(prev));
+
+        let mut polylines = Vec::new();
+        let mut polyline = Vec::new();
+        for event in paths.iter().flat_map(PathDescriptor::world_segments) {
+            match event {
+                PathEvent::Begin { at } => polyline.push(Vec2::new(at.x, at.y)),
+                PathEvent::Line { to, .. } => polyline.push(Vec2::new(to.x, to.y)),
+                PathEvent::Quadratic { from, ctrl, to } => polyline.extend(
+                    QuadraticBezierSegment { from, ctrl, to }.flattened(tolerance).map(|p| Vec2::new(p.x, p.y))
+                ),
+                PathEvent::Cubic { from, ctrl1, ctrl2, to } => polyline.extend(
+                    CubicBezierSegment { from, ctrl1, ctrl2, to }.flattened(tolerance).map(|p| Vec2::new(p.x, p.y))
+                ),
+                PathEvent::End { .. } => {
+                    if polyline.len() > 1 && polyline.first() == polyline.last() {
+                        polyline.pop();
+                    }
+                    polylines.push(std::mem::take(&mut polyline));
+                }
+            }
+        }
+
+        polylines
+    }
+}
+
+impl PathDescriptor {
+    /// The segments of the path with its `abs_transform` applied, e.g. to build colliders
+    /// or custom line rendering from the outline.
+    pub fn world_segments(&self) -> Vec<PathEvent> {
+        let world = |point: Point| {
+            let point = self.abs_transform * Vec3::new(point.x, point.y, 0.0);
+            Point::new(point.x, point.y)
+        };
+        self.segments.iter()
+            .map(|event| match *event {
+                PathEvent::Begin { at } => PathEvent::Begin { at: world(at) },
+                PathEvent::Line { from, to } => PathEvent::Line { from: world(from), to: world(to) },
+                PathEvent::Quadratic { from, ctrl, to } => PathEvent::Quadratic {
+                    from: world(from), ctrl: world(ctrl), to: world(to)
+                },
+                PathEvent::Cubic { from, ctrl1, ctrl2, to } => PathEvent::Cubic {
+                    from: world(from), ctrl1: world(ctrl1), ctrl2: world(ctrl2), to: world(to)
+                },
+                PathEvent::End { last, first, close } => PathEvent::End {
+                    last: world(last), first: world(first), close
+                },
+            })
+            .collect()
+    }
+
+    /// The bounds of the path after applying its `abs_transform`, as `(min, max)` corners.
+    /// Control points are included, so the bounds of curves may be slightly too large.
+    /// Returns `None` for a path without segments.
+    pub fn bounds(&self) -> Option<(Vec2, Vec2)> {
+        self.segments.iter()
+            .flat_map(|event| match *event {
+                PathEvent::Begin { at } => vec![at],
+                PathEvent::Line { from, to } => vec![from, to],
+                PathEvent::Quadratic { from, ctrl, to } => vec![from, ctrl, to],
+                PathEvent::Cubic { from, ctrl1, ctrl2, to } => vec![from, ctrl1, ctrl2, to],
+                PathEvent::End { last, first, .. } => vec![last, first],
+            })
+            .map(|point| (self.abs_transform * Vec3::new(point.x, point.y, 0.0)).truncate())
+            .fold(None, |bounds, point| match bounds {
+                Some((min, max)) => Some((point.min(min), point.max(max))),
+                None => Some((point, point)),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::svg::load_test_svg;
+
+    use super::*;
+
+    #[test]
+    fn path_events_world_apply_the_transform() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 30 20">
+            <rect width="10" height="5" transform="translate(5 5) scale(2)"/>
+        </svg>"#);
+        let events = svg.path_events_world();
+
+        assert_eq!(events.len(), 1);
+        let points: Vec<Vec2> = events[0].iter()
+            .filter_map(|event| match *event {
+                PathEvent::Begin { at } => Some(at),
+                PathEvent::Line { to, .. } => Some(to),
+                _ => None,
+            })
+            .map(|point| Vec2::new(point.x, point.y))
+            .collect();
+        for expected in [Vec2::new(5.0, 5.0), Vec2::new(25.0, 5.0), Vec2::new(25.0, 15.0), Vec2::new(5.0, 15.0)] {
+            assert!(points.iter().any(|point| point.distance(expected) < 1e-4), "{} is missing in {:?}", expected, points);
+        }
+    }
+
+    #[test]
+    fn colliders_yield_one_polyline_per_outline() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <path d="M 0 0 L 10 0 L 0 10 Z" fill="red" stroke="blue"/>
+        </svg>"#);
+        let colliders = svg.colliders(0.1);
+
+        assert_eq!(svg.paths.len(), 2);
+        assert_eq!(colliders, vec![vec![Vec2::new(0.0, 0.0), Vec2::new(10.0, 0.0), Vec2::new(0.0, 10.0)]]);
+    }
+}
@@ -27,17 +27,32 @@
     clippy::cargo
 )]
 
+mod batch;
 mod bundle;
+mod debug;
+mod geometry;
 mod loader;
 mod plugin;
 mod svg;
 mod tessellation;
 mod vertex_buffer;
 
+pub use loader::SvgError;
+pub use tessellation::{tessellate_bytes, tessellate_file};
+pub use vertex_buffer::MeshData;
+/// The usvg version this crate parses with, e.g. for [`Svg::from_tree`](prelude::Svg::from_tree).
+pub use usvg;
+
 /// Import this module as `use bevy_svg::prelude::*` to get
 /// convenient imports.
 pub mod prelude {
-    pub use crate::{bundle::SvgBundle, plugin::SvgPlugin, svg::{Svg, Origin}};
+    pub use crate::{
+        batch::SvgBatch,
+        bundle::SvgBundle,
+        loader::{NodeAction, NodeHook, SvgLoaderSettings},
+        plugin::{SvgColorOverrides, SvgPathRanges, SvgPlugin, SvgReadyEvent, SvgState, SvgTessellationBudget},
+        svg::{DrawType, Origin, PathDescriptor, QualityLevel, Svg, DEFAULT_FILL_TOLERANCE, DEFAULT_STROKE_TOLERANCE},
+    };
     pub use lyon_tessellation::{
         FillOptions, FillRule, LineCap, LineJoin, Orientation, StrokeOptions,
     };
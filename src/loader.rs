@@ -1,12 +1,162 @@
+use std::{io::Read, sync::Arc};
+
 use anyhow;
-use bevy::{asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset}, prelude::info};
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    ecs::world::{FromWorld, World},
+    prelude::info,
+};
+use flate2::read::GzDecoder;
 use thiserror::Error;
 
 use crate::prelude::Svg;
 
 
-#[derive(Default)]
-pub struct SvgAssetLoader;
+/// What happens to a node of the SVG, as decided by [`SvgLoaderSettings::node_hook`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeAction {
+    /// Render the node as usual.
+    Render,
+    /// Skip the node itself, its descendants are still rendered.
+    Skip,
+    /// Skip the node and all of its descendants.
+    SkipSubtree,
+}
+
+/// Called for every node of an SVG while it is converted, see [`SvgLoaderSettings::node_hook`].
+pub type NodeHook = Arc<dyn Fn(&usvg::Node) -> NodeAction + Send + Sync>;
+
+/// Settings used by the [`SvgAssetLoader`] when parsing SVG files.
+///
+/// The loader reads this resource once when it is created, so insert it
+/// before adding the [`SvgPlugin`](crate::prelude::SvgPlugin).
+#[derive(Clone)]
+pub struct SvgLoaderSettings {
+    /// Target DPI, used to resolve physical units like `pt`, `in` or `cm` to pixels.
+    pub dpi: f64,
+    /// Treat the colors in SVG files as premultiplied by their alpha and convert them
+    /// to straight alpha. Some design tools export colors this way.
+    /// Color channels that end up out of range are clamped.
+    pub premultiplied_colors: bool,
+    /// Font family used for text that doesn't specify one.
+    ///
+    /// This is not a fallback: text with a `font-family` that can't be found in the font
+    /// database isn't rendered, usvg only logs a warning for it while parsing.
+    pub font_family: String,
+    /// Load the fonts installed on the system. Disable this for reproducible text rendering,
+    /// so that only the fonts in `./assets` are used, no matter which machine runs the app.
+    pub load_system_fonts: bool,
+    /// Minimum width of strokes. Thinner strokes are widened to this width, so that
+    /// hairlines in e.g. technical diagrams stay visible.
+    pub min_stroke_width: f32,
+    /// Maximum size of an SVG file in bytes, larger files are rejected before parsing.
+    /// A safety valve for apps that load user supplied SVGs, `None` allows any size.
+    /// For compressed `.svgz` files the limit applies to the decompressed data.
+    ///
+    /// External entities are never resolved by the XML parser, whatever this is set to.
+    /// Internal entities are expanded by the XML parser, this limit doesn't bound their
+    /// expanded size. The default `None` bounds neither the file nor the expansion.
+    pub max_file_size: Option<usize>,
+    /// File extensions the loader is registered for, without the leading dot.
+    pub extensions: Vec<&'static str>,
+    /// Called for every node of the parsed [`usvg::Tree`] before it is converted, e.g. to skip
+    /// nodes by id or to log them. Skipping a group only skips its descendants with
+    /// [`NodeAction::SkipSubtree`]. Nodes in `<defs>` are never passed to the hook.
+    pub node_hook: Option<NodeHook>,
+}
+
+impl std::fmt::Debug for SvgLoaderSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SvgLoaderSettings")
+            .field("dpi", &self.dpi)
+            .field("premultiplied_colors", &self.premultiplied_colors)
+            .field("font_family", &self.font_family)
+            .field("load_system_fonts", &self.load_system_fonts)
+            .field("min_stroke_width", &self.min_stroke_width)
+            .field("max_file_size", &self.max_file_size)
+            .field("extensions", &self.extensions)
+            .field("node_hook", &self.node_hook.as_ref().map(|_| "Fn(&usvg::Node) -> NodeAction"))
+            .finish()
+    }
+}
+
+impl Default for SvgLoaderSettings {
+    fn default() -> Self {
+        Self {
+            dpi: 96.0,
+            premultiplied_colors: false,
+            font_family: usvg::Options::default().font_family,
+            load_system_fonts: true,
+            min_stroke_width: 0.0,
+            max_file_size: None,
+            extensions: vec!["svg", "svgz"],
+            node_hook: None,
+        }
+    }
+}
+
+impl SvgLoaderSettings {
+    /// The [`usvg::Options`] used to parse an SVG with these settings, e.g. to parse a
+    /// [`usvg::Tree`] for [`Svg::from_tree`] the same way the loader does it.
+    ///
+    /// Unlike [`usvg::Options::default`], named groups are kept, so they can be extracted
+    /// with [`Svg::sub_svg`].
+    pub fn to_usvg_options(&self) -> usvg::Options {
+        let mut opts = usvg::Options::default();
+        opts.dpi = self.dpi;
+        opts.font_family = self.font_family.clone();
+        // Keep groups with an id, so they can be extracted with `Svg::sub_svg`.
+        opts.keep_named_groups = true;
+        if self.load_system_fonts {
+            opts.fontdb.load_system_fonts();
+        }
+        opts.fontdb.load_fonts_dir("./assets");
+
+        opts
+    }
+
+    /// Parses `bytes` into a [`usvg::Tree`] with these settings.
+    pub(crate) fn parse(&self, bytes: &[u8]) -> Result<usvg::Tree, SvgError> {
+        let inflated;
+        let bytes = match self.max_file_size {
+            Some(max_size) => {
+                if bytes.len() > max_size {
+                    return Err(SvgError::FileTooLarge { size: bytes.len(), max_size });
+                }
+
+                // Compressed files are inflated through a bounded reader, so that a small
+                // file can't inflate into an arbitrarily large one.
+                if bytes.starts_with(&[0x1f, 0x8b]) {
+                    let mut data = Vec::new();
+                    GzDecoder::new(bytes).take(max_size as u64 + 1).read_to_end(&mut data)?;
+                    if data.len() > max_size {
+                        return Err(SvgError::FileTooLarge { size: data.len(), max_size });
+                    }
+                    inflated = data;
+                    &inflated[..]
+                } else {
+                    bytes
+                }
+            }
+            None => bytes,
+        };
+
+        let opts = self.to_usvg_options();
+        Ok(usvg::Tree::from_data(bytes, &opts.to_ref())?)
+    }
+}
+
+pub struct SvgAssetLoader {
+    settings: SvgLoaderSettings,
+}
+
+impl FromWorld for SvgAssetLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            settings: world.get_resource::<SvgLoaderSettings>().cloned().unwrap_or_default(),
+        }
+    }
+}
 
 impl AssetLoader for SvgAssetLoader {
     fn load<'a>(
@@ -15,19 +165,15 @@ impl AssetLoader for SvgAssetLoader {
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
         Box::pin(async move {
-            let mut opts = usvg::Options::default();
-            opts.fontdb.load_system_fonts();
-            opts.fontdb.load_fonts_dir("./assets");
-
             info!("Parsing SVG: {}", load_context.path().display());
-            let svg_tree = usvg::Tree::from_data(&bytes, &opts.to_ref()).map_err(|err| {
+            let svg_tree = self.settings.parse(bytes).map_err(|error| {
                 FileSvgError {
-                    error: err.into(),
+                    error,
                     path: format!("{}", load_context.path().display()),
                 }
             })?;
 
-            let mut svg = Svg::from_tree(svg_tree);
+            let mut svg = Svg::from_tree_with_settings(svg_tree, &self.settings);
             let name = &load_context.path().file_name().ok_or_else(||
                 FileSvgError {
                     error: SvgError::InvalidFileName(load_context.path().display().to_string()),
@@ -43,17 +189,48 @@ impl AssetLoader for SvgAssetLoader {
     }
 
     fn extensions(&self) -> &[&str] {
-        &["svg", "svgz"]
+        &self.settings.extensions
     }
 }
 
 /// An error that occurs when loading a texture
 #[derive(Error, Debug)]
 pub enum SvgError {
+    /// The file name of the SVG is not valid.
     #[error("invalid file name")]
     InvalidFileName(String),
+    /// usvg failed to parse the SVG.
     #[error("failed to load an SVG: {0}")]
     SvgError(#[from] usvg::Error),
+    /// The SVG file, or its decompressed data, is larger than the configured maximum size.
+    #[error("SVG data of {size} bytes exceeds the maximum size of {max_size} bytes")]
+    FileTooLarge {
+        /// Size of the data, compressed files are only decompressed until they exceed the maximum.
+        size: usize,
+        /// The configured maximum size.
+        max_size: usize,
+    },
+    /// Lyon failed to tessellate a path.
+    #[error("failed to tessellate path `{path}` of SVG `{svg}`: {error}")]
+    Tessellation {
+        /// Name of the SVG.
+        svg: String,
+        /// Id of the path that failed, empty if it has none.
+        path: String,
+        /// The error reported by lyon.
+        error: String,
+    },
+    /// A path produced NaN or infinite vertices, e.g. because of a degenerate transform.
+    #[error("path `{path}` of SVG `{svg}` has NaN or infinite vertices")]
+    NonFinite {
+        /// Name of the SVG.
+        svg: String,
+        /// Id of the path, empty if it has none.
+        path: String,
+    },
+    /// The SVG file could not be read.
+    #[error("failed to read an SVG file: {0}")]
+    Io(#[from] std::io::Error),
 }
 
 /// An error that occurs when loading a texture from a file.
@@ -71,3 +248,88 @@ impl std::fmt::Display for FileSvgError {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::svg::test_settings;
+
+    #[test]
+    fn dpi_scales_physical_units() {
+        let data = br#"<svg xmlns="http://www.w3.org/2000/svg" width="1in" height="2in" viewBox="0 0 10 20">
+            <rect width="10" height="20"/>
+        </svg>"#;
+        let at_72 = Svg::from_bytes(data, "test", &SvgLoaderSettings { dpi: 72.0, ..test_settings() }).unwrap();
+        let at_96 = Svg::from_bytes(data, "test", &SvgLoaderSettings { dpi: 96.0, ..test_settings() }).unwrap();
+
+        assert!((at_72.width - 72.0).abs() < 1e-6);
+        assert!((at_96.width - 96.0).abs() < 1e-6);
+        assert!((at_96.height / at_72.height - 96.0 / 72.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn from_tree_matches_from_bytes() {
+        let data = br#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <g id="icon"><rect width="10" height="10" fill="red"/></g>
+        </svg>"#;
+        let options = test_settings().to_usvg_options();
+        let tree = usvg::Tree::from_data(data, &options.to_ref()).unwrap();
+        let from_tree = Svg::from_tree(tree);
+        let from_bytes = Svg::from_bytes(data, "test", &test_settings()).unwrap();
+
+        assert_eq!(from_tree.paths.len(), from_bytes.paths.len());
+        assert_eq!(from_tree.paths[0].color, from_bytes.paths[0].color);
+        assert_eq!(from_tree.paths[0].bounds(), from_bytes.paths[0].bounds());
+        assert!(from_tree.sub_svg("icon").is_some());
+    }
+
+    #[test]
+    fn system_fonts_are_skipped_when_disabled() {
+        assert_eq!(test_settings().to_usvg_options().fontdb.len(), 0);
+    }
+
+    #[test]
+    fn loader_is_registered_for_the_configured_extensions() {
+        let mut world = World::new();
+        world.insert_resource(SvgLoaderSettings { extensions: vec!["icon"], ..test_settings() });
+        let loader = SvgAssetLoader::from_world(&mut world);
+
+        assert_eq!(loader.extensions(), &["icon"]);
+    }
+
+    #[test]
+    fn external_entities_are_not_resolved() {
+        let data = br#"<?xml version="1.0"?>
+<!DOCTYPE svg [<!ENTITY x SYSTEM "file:///etc/passwd">]>
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+    <rect id="leak&x;" width="10" height="10"/>
+</svg>"#;
+
+        // Either the parser rejects the document, or the entity is dropped.
+        if let Ok(svg) = Svg::from_bytes(data, "test", &test_settings()) {
+            assert!(svg.paths.iter().all(|path| !path.id.contains("root:")));
+        }
+    }
+
+    #[test]
+    fn max_file_size_applies_to_decompressed_data() {
+        use std::io::Write;
+
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10"><!--{}--><rect width="10" height="10"/></svg>"#,
+            " ".repeat(100_000)
+        );
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(svg.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert!(compressed.len() < 10_000);
+
+        let limited = SvgLoaderSettings { max_file_size: Some(10_000), ..test_settings() };
+        let error = Svg::from_bytes(&compressed, "test", &limited).unwrap_err();
+        assert!(matches!(error, SvgError::FileTooLarge { size: 10_001, max_size: 10_000 }));
+        assert!(error.to_string().contains("10000 bytes"), "{}", error);
+
+        let large_enough = SvgLoaderSettings { max_file_size: Some(1_000_000), ..test_settings() };
+        assert_eq!(Svg::from_bytes(&compressed, "test", &large_enough).unwrap().paths.len(), 1);
+    }
+}
@@ -11,20 +11,24 @@
 //! that creates a mesh for each entity that has been spawned as a
 //! `SvgBundle`.
 
-use crate::{Convert, svg::Svg, tessellation, loader::SvgAssetLoader, prelude::Origin};
+use std::{collections::VecDeque, ops::Range};
+
+use crate::{Convert, svg::{remapped_color, PathDescriptor, Svg}, tessellation, loader::{SvgAssetLoader, SvgLoaderSettings}, prelude::Origin};
 use bevy::{
     app::{App, Plugin},
     asset::{AddAsset, Assets, Handle, HandleUntyped},
     ecs::{
         schedule::{StageLabel, SystemStage},
-        system::{Query, Res, ResMut}
+        query::Changed,
+        system::{Local, Query, Res, ResMut}
     },
     reflect::TypeUuid,
     render::{
         mesh::Mesh,
         pipeline::PipelineDescriptor,
         shader::{Shader, ShaderStage, ShaderStages}
-    }, prelude::{info, AssetEvent, EventReader, Entity, Transform}, math::Vec3,
+    }, prelude::{info, AssetEvent, Color, Component, EventReader, EventWriter, Entity},
+    utils::HashSet,
 };
 use lyon_tessellation::{FillTessellator, StrokeTessellator};
 
@@ -37,6 +41,84 @@ pub enum Stage {
     SVG,
 }
 
+/// Event sent once the meshes of an [`SvgBundle`](crate::bundle::SvgBundle) have been built.
+#[derive(Clone, Debug)]
+pub struct SvgReadyEvent {
+    /// The entity whose mesh was built.
+    pub entity: Entity,
+    /// The [`Svg`] the mesh was built from.
+    pub handle: Handle<Svg>,
+}
+
+/// Progress of building the mesh of an [`SvgBundle`](crate::bundle::SvgBundle).
+///
+/// Can be polled every frame, as an alternative to listening for [`SvgReadyEvent`]s.
+#[derive(Clone, Component, Copy, Debug, PartialEq, Eq)]
+pub enum SvgState {
+    /// The [`Svg`] asset is still being loaded.
+    Loading,
+    /// The [`Svg`] is loaded and waits for its mesh to be built.
+    Tessellating,
+    /// The mesh is built and up to date with the [`Svg`].
+    Ready,
+}
+
+impl Default for SvgState {
+    fn default() -> Self {
+        SvgState::Loading
+    }
+}
+
+/// Colors that replace the ones of the [`Svg`] for a single entity.
+///
+/// Changing this component only rewrites the vertex colors of the entity's mesh, without
+/// tessellating the [`Svg`] again, so color animations are cheap. It needs the
+/// [`SvgPathRanges`] of the [`SvgBundle`](crate::bundle::SvgBundle) to find the vertices of a path.
+#[derive(Clone, Component, Debug, Default)]
+pub struct SvgColorOverrides {
+    /// Colors for the paths with the given id, or inside a group with that id.
+    /// Later entries win over earlier ones, and all of them win over `by_color`.
+    pub by_id: Vec<(String, Color)>,
+    /// Pairs of `(from, to)` colors, matched like [`Svg::remap_color`] does it, e.g. to swap
+    /// the palette of a single entity. Later entries win over earlier ones.
+    pub by_color: Vec<(Color, Color)>,
+}
+
+impl SvgColorOverrides {
+    /// The color `path` is drawn with, after applying the overrides.
+    pub fn color_of(&self, path: &PathDescriptor) -> Color {
+        if let Some((_, color)) = self.by_id.iter().rev().find(|(id, _)| path.has_id(id)) {
+            return *color;
+        }
+
+        self.by_color.iter().rev()
+            .find_map(|(from, to)| remapped_color(path.color, *from, *to))
+            .unwrap_or(path.color)
+    }
+}
+
+/// The vertices every path of the [`Svg`] occupies in the mesh of an entity, kept up to date
+/// by the [`SvgPlugin`].
+#[derive(Clone, Component, Debug, Default)]
+pub struct SvgPathRanges(Vec<Range<usize>>);
+
+impl SvgPathRanges {
+    /// The vertices of the path at `index` in [`Svg::paths`], empty if it failed to tessellate.
+    pub fn get(&self, index: usize) -> Option<Range<usize>> {
+        self.0.get(index).cloned()
+    }
+}
+
+/// Limits how many SVG meshes the [`SvgPlugin`] builds per frame.
+///
+/// Spawning many SVGs at once can cause a frame spike, with a budget the work is spread
+/// over multiple frames instead, at the cost of some SVGs showing up a bit later.
+#[derive(Clone, Debug, Default)]
+pub struct SvgTessellationBudget {
+    /// Maximum number of meshes built per frame, `None` builds all of them right away.
+    pub max_meshes_per_frame: Option<usize>,
+}
+
 /// A plugin that provides resources and a system to draw [`SvgBundle`]s in Bevy with..
 pub struct SvgPlugin;
 
@@ -46,6 +128,9 @@ impl Plugin for SvgPlugin {
         let stroke_tess = StrokeTessellator::new();
         app
             .add_asset::<Svg>()
+            .add_event::<SvgReadyEvent>()
+            .init_resource::<SvgLoaderSettings>()
+            .init_resource::<SvgTessellationBudget>()
             .init_asset_loader::<SvgAssetLoader>()
             .insert_resource(fill_tess)
             .insert_resource(stroke_tess)
@@ -55,7 +140,8 @@ impl Plugin for SvgPlugin {
                 Stage::SVG,
                 SystemStage::parallel(),
             )
-            .add_system_to_stage(Stage::SVG, svg_mesh_maker);
+            .add_system_to_stage(Stage::SVG, svg_mesh_maker)
+            .add_system_to_stage(Stage::SVG, svg_recolor);
     }
 }
 
@@ -64,53 +150,137 @@ fn setup(
     mut shaders: ResMut<Assets<Shader>>,
 ) {
     // Create a new shader pipeline
-    pipelines.set_untracked(
-        SVG_PIPELINE_HANDLE,
-        PipelineDescriptor::default_config(ShaderStages {
-            vertex: shaders.add(Shader::from_glsl(ShaderStage::Vertex, VERTEX_SHADER)),
-            fragment: Some(shaders.add(Shader::from_glsl(ShaderStage::Fragment, FRAGMENT_SHADER))),
-        })
-    );
+    let mut descriptor = PipelineDescriptor::default_config(ShaderStages {
+        vertex: shaders.add(Shader::from_glsl(ShaderStage::Vertex, VERTEX_SHADER)),
+        fragment: Some(shaders.add(Shader::from_glsl(ShaderStage::Fragment, FRAGMENT_SHADER))),
+    });
+    // Mirrored SVGs, e.g. with a negative `Transform` scale, flip the winding of their triangles.
+    descriptor.primitive.cull_mode = None;
+    pipelines.set_untracked(SVG_PIPELINE_HANDLE, descriptor);
 }
 
 /// Bevy system which queries all [`SvgBundle`]s to complete them with a mesh and material.
+///
+/// A mesh is (re)built when the [`Svg`] asset of an entity is created or modified, or when the
+/// `Handle<Svg>` of an entity changes, e.g. to swap an icon in place.
 fn svg_mesh_maker(
     mut svg_events: EventReader<AssetEvent<Svg>>,
     svgs: Res<Assets<Svg>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut fill_tess: ResMut<FillTessellator>,
     mut stroke_tess: ResMut<StrokeTessellator>,
-    mut query: Query<
-        (Entity, &Handle<Svg>, &mut Handle<Mesh>, &Origin, &mut Transform),
-    >,
+    mut ready_events: EventWriter<SvgReadyEvent>,
+    budget: Res<SvgTessellationBudget>,
+    mut pending: Local<VecDeque<Entity>>,
+    // The entities in `pending`, to skip duplicates without searching the queue.
+    mut queued: Local<HashSet<Entity>>,
+    mut query: Query<(
+        Entity, &Handle<Svg>, &mut Handle<Mesh>, &Origin, Option<&mut SvgState>,
+        Option<&mut SvgPathRanges>, Option<&SvgColorOverrides>,
+    )>,
+    changed_svgs: Query<Entity, Changed<Handle<Svg>>>,
 ) {
+    let mut outdated: Vec<Entity> = changed_svgs.iter().collect();
     for event in svg_events.iter() {
         match event {
-            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
-                let bundle = query.iter_mut().filter(|(_, svg, _, _, _)| svg == &handle).next();
-                if let Some((_, _, mut mesh, origin, mut transform)) = bundle {
-                    let svg = svgs.get(handle).unwrap();
-                    let translation = match origin {
-                        Origin::Center => transform.translation + Vec3::new(
-                            -svg.width as f32 * transform.scale.x / 2.0,
-                            svg.height as f32 * transform.scale.y / 2.0,
-                            0.0
-                        ),
-                        Origin::TopLeft => transform.translation,
-                    };
-                    transform.translation = translation;
-
-                    info!("Make mesh for SVG: {}", svg.name);
-                    let buffer = tessellation::generate_buffer(&svg, &mut fill_tess, &mut stroke_tess);
-                    *mesh = meshes.add(buffer.convert());
-                }
+            AssetEvent::Created { handle } => {
+                // Entities spawned in the frame the asset was added are already built from it.
+                outdated.extend(
+                    query.iter_mut()
+                        .filter(|(_, svg, _, _, state, ..)| svg == &handle && state.as_deref() != Some(&SvgState::Ready))
+                        .map(|(entity, ..)| entity)
+                );
+            },
+            AssetEvent::Modified { handle } => {
+                outdated.extend(
+                    query.iter_mut()
+                        .filter(|(_, svg, ..)| svg == &handle)
+                        .map(|(entity, ..)| entity)
+                );
             },
             AssetEvent::Removed { handle } => {
-                let _bundle = query.iter_mut().filter(|(_, svg, _, _, _)| svg == &handle).next();
+                let _bundle = query.iter_mut().filter(|(_, svg, ..)| svg == &handle).next();
                 //TODO:
             },
         }
     }
+    for entity in outdated {
+        if let Ok((_, handle, _, _, Some(mut state), ..)) = query.get_mut(entity) {
+            *state = if svgs.get(handle).is_some() { SvgState::Tessellating } else { SvgState::Loading };
+        }
+        if queued.insert(entity) {
+            pending.push_back(entity);
+        }
+    }
+
+    let max_meshes = budget.max_meshes_per_frame.unwrap_or(usize::MAX);
+    let mut built = 0;
+    while built < max_meshes {
+        let entity = match pending.pop_front() {
+            Some(entity) => entity,
+            None => break,
+        };
+        queued.remove(&entity);
+
+        if let Ok((entity, handle, mut mesh, origin, state, path_ranges, overrides)) = query.get_mut(entity) {
+            // The asset may not be loaded yet, its `Created` event will trigger the build.
+            if let Some(svg) = svgs.get(handle) {
+                info!("Make mesh for SVG: {}", svg.name);
+                // The origin is baked into the mesh, so the entity's `Transform` stays untouched.
+                // Replacing the handle drops the previous mesh.
+                let (buffer, ranges) = tessellation::generate_ranged_buffer(&svg, *origin, &mut fill_tess, &mut stroke_tess);
+                // SVGs without drawable content, like symbol libraries with only `<defs>`,
+                // get no mesh instead of an empty one.
+                *mesh = if buffer.vertices.is_empty() {
+                    Default::default()
+                } else {
+                    let mut new_mesh: Mesh = buffer.convert();
+                    if let Some(overrides) = overrides {
+                        new_mesh.set_attribute(Mesh::ATTRIBUTE_COLOR, vertex_colors(svg, &ranges, overrides));
+                    }
+                    meshes.add(new_mesh)
+                };
+                if let Some(mut path_ranges) = path_ranges {
+                    path_ranges.0 = ranges;
+                }
+                ready_events.send(SvgReadyEvent { entity, handle: handle.clone_weak() });
+                if let Some(mut state) = state {
+                    *state = SvgState::Ready;
+                }
+                built += 1;
+            }
+        }
+    }
+}
+
+/// Bevy system which rewrites the vertex colors of entities whose [`SvgColorOverrides`] changed.
+fn svg_recolor(
+    svgs: Res<Assets<Svg>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    query: Query<
+        (&Handle<Svg>, &Handle<Mesh>, &SvgPathRanges, &SvgColorOverrides),
+        Changed<SvgColorOverrides>,
+    >,
+) {
+    for (svg, mesh, ranges, overrides) in query.iter() {
+        // Meshes that are not built yet get the overrides when they are built.
+        if let (Some(svg), Some(mesh)) = (svgs.get(svg), meshes.get_mut(mesh)) {
+            let vertices = ranges.0.last().map_or(0, |range| range.end);
+            if ranges.0.len() == svg.paths.len() && vertices == mesh.count_vertices() {
+                mesh.set_attribute(Mesh::ATTRIBUTE_COLOR, vertex_colors(svg, &ranges.0, overrides));
+            }
+        }
+    }
+}
+
+/// The vertex colors of a mesh built from `svg`, with the `overrides` applied.
+fn vertex_colors(svg: &Svg, ranges: &[Range<usize>], overrides: &SvgColorOverrides) -> Vec<[f32; 4]> {
+    let mut colors = vec![[0.0; 4]; ranges.last().map_or(0, |range| range.end)];
+    for (path, range) in svg.paths.iter().zip(ranges) {
+        colors[range.clone()].fill(overrides.color_of(path).as_linear_rgba_f32());
+    }
+
+    colors
 }
 
 const VERTEX_SHADER: &str = r#"
@@ -142,3 +312,243 @@ void main() {
     o_Target = v_color;
 }
 "#;
+
+#[cfg(test)]
+mod tests {
+    use bevy::{asset::{AssetPlugin, HandleId}, prelude::{CoreStage, MinimalPlugins}};
+
+    use super::*;
+    use crate::{bundle::SvgBundle, svg::load_test_svg};
+
+    const RECT: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+        <rect width="10" height="10" fill="red"/>
+    </svg>"#;
+
+    const TWO_RECTS: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 30 10">
+        <rect id="left" width="10" height="10" fill="red"/>
+        <rect id="right" x="20" width="10" height="10" fill="blue"/>
+    </svg>"#;
+
+    /// Entities of all [`SvgReadyEvent`]s sent so far.
+    #[derive(Default)]
+    struct Ready(Vec<Entity>);
+
+    fn collect_ready(mut events: EventReader<SvgReadyEvent>, mut ready: ResMut<Ready>) {
+        ready.0.extend(events.iter().map(|event| event.entity));
+    }
+
+    /// Handles of all meshes an [`AssetEvent::Modified`] was sent for so far.
+    #[derive(Default)]
+    struct ModifiedMeshes(Vec<Handle<Mesh>>);
+
+    fn collect_modified_meshes(mut events: EventReader<AssetEvent<Mesh>>, mut modified: ResMut<ModifiedMeshes>) {
+        modified.0.extend(events.iter().filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.clone_weak()),
+            _ => None,
+        }));
+    }
+
+    /// An app with the plugin and the assets it needs, but without a renderer.
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<Shader>()
+            .add_asset::<PipelineDescriptor>()
+            .add_plugin(SvgPlugin)
+            .init_resource::<Ready>()
+            .add_system_to_stage(CoreStage::PostUpdate, collect_ready);
+        app
+    }
+
+    fn add_svg(app: &mut App, data: &str) -> Handle<Svg> {
+        app.world.get_resource_mut::<Assets<Svg>>().unwrap().add(load_test_svg(data))
+    }
+
+    fn spawn(app: &mut App, svg: Handle<Svg>) -> Entity {
+        app.world.spawn().insert_bundle(SvgBundle { svg, ..Default::default() }).id()
+    }
+
+    fn mesh(app: &App, entity: Entity) -> Option<&Mesh> {
+        let handle = app.world.get::<Handle<Mesh>>(entity)?;
+        app.world.get_resource::<Assets<Mesh>>()?.get(handle)
+    }
+
+    #[test]
+    fn ready_event_is_sent_once_per_entity() {
+        let mut app = app();
+        let handle = add_svg(&mut app, RECT);
+        let first = spawn(&mut app, handle.clone());
+        let second = spawn(&mut app, handle);
+        for _ in 0..3 {
+            app.update();
+        }
+
+        let ready = &app.world.get_resource::<Ready>().unwrap().0;
+        assert_eq!(ready.len(), 2);
+        assert!(ready.contains(&first));
+        assert!(ready.contains(&second));
+        assert!(mesh(&app, first).is_some());
+    }
+
+    #[test]
+    fn changing_the_handle_rebuilds_the_mesh() {
+        let mut app = app();
+        let rect = add_svg(&mut app, RECT);
+        let two_rects = add_svg(&mut app, TWO_RECTS);
+        let entity = spawn(&mut app, rect);
+        app.update();
+        let before = mesh(&app, entity).unwrap().count_vertices();
+
+        *app.world.get_mut::<Handle<Svg>>(entity).unwrap() = two_rects;
+        app.update();
+
+        assert_eq!(mesh(&app, entity).unwrap().count_vertices(), 2 * before);
+        assert_eq!(app.world.get::<SvgState>(entity), Some(&SvgState::Ready));
+    }
+
+    #[test]
+    fn color_overrides_only_rewrite_the_colors_of_matching_paths() {
+        let mut app = app();
+        let handle = add_svg(&mut app, TWO_RECTS);
+        let entity = spawn(&mut app, handle);
+        app.world.entity_mut(entity).insert(SvgColorOverrides::default());
+        app.update();
+
+        let mesh_handle = app.world.get::<Handle<Mesh>>(entity).unwrap().clone();
+        let bytes = |app: &App, attribute: &'static str| mesh(app, entity).unwrap().attribute(attribute).unwrap().get_bytes().to_vec();
+        let positions = bytes(&app, Mesh::ATTRIBUTE_POSITION);
+        let colors = bytes(&app, Mesh::ATTRIBUTE_COLOR);
+
+        app.world.get_mut::<SvgColorOverrides>(entity).unwrap().by_id.push(("right".to_string(), Color::GREEN));
+        app.update();
+
+        let ranges = app.world.get::<SvgPathRanges>(entity).unwrap().clone();
+        let (left, right) = (ranges.get(0).unwrap(), ranges.get(1).unwrap());
+        let recolored = bytes(&app, Mesh::ATTRIBUTE_COLOR);
+        let color_size = std::mem::size_of::<[f32; 4]>();
+        assert_eq!(app.world.get::<Handle<Mesh>>(entity), Some(&mesh_handle));
+        assert_eq!(bytes(&app, Mesh::ATTRIBUTE_POSITION), positions);
+        assert_eq!(
+            recolored[left.start * color_size..left.end * color_size],
+            colors[left.start * color_size..left.end * color_size]
+        );
+        assert_ne!(
+            recolored[right.start * color_size..right.end * color_size],
+            colors[right.start * color_size..right.end * color_size]
+        );
+    }
+
+    #[test]
+    fn budget_spreads_the_builds_over_frames_without_duplicates() {
+        let mut app = app();
+        app.world.get_resource_mut::<SvgTessellationBudget>().unwrap().max_meshes_per_frame = Some(1);
+        let handle = add_svg(&mut app, RECT);
+        let entities: Vec<Entity> = (0..3).map(|_| spawn(&mut app, handle.clone())).collect();
+
+        for built in 1..=3 {
+            app.update();
+            assert_eq!(app.world.get_resource::<Ready>().unwrap().0.len(), built);
+        }
+        app.update();
+
+        let mut ready = app.world.get_resource::<Ready>().unwrap().0.clone();
+        ready.sort();
+        let mut expected = entities.clone();
+        expected.sort();
+        assert_eq!(ready, expected);
+    }
+
+    #[test]
+    fn color_overrides_remap_colors_of_a_single_entity() {
+        let mut app = app();
+        let handle = add_svg(&mut app, TWO_RECTS);
+        let remapped = spawn(&mut app, handle.clone());
+        let untouched = spawn(&mut app, handle);
+        app.world.entity_mut(remapped).insert(SvgColorOverrides {
+            by_color: vec![(Color::rgb(0.0, 0.0, 1.0), Color::GREEN)],
+            ..Default::default()
+        });
+        app.update();
+
+        let colors = |app: &App, entity| mesh(app, entity).unwrap().attribute(Mesh::ATTRIBUTE_COLOR).unwrap().get_bytes().to_vec();
+        let ranges = app.world.get::<SvgPathRanges>(remapped).unwrap().clone();
+        let (left, right) = (ranges.get(0).unwrap(), ranges.get(1).unwrap());
+        let color_size = std::mem::size_of::<[f32; 4]>();
+        let (remapped, untouched) = (colors(&app, remapped), colors(&app, untouched));
+        assert_eq!(
+            remapped[left.start * color_size..left.end * color_size],
+            untouched[left.start * color_size..left.end * color_size]
+        );
+        assert_ne!(
+            remapped[right.start * color_size..right.end * color_size],
+            untouched[right.start * color_size..right.end * color_size]
+        );
+    }
+
+    #[test]
+    fn state_goes_from_loading_to_ready() {
+        let mut app = app();
+        let handle = Handle::<Svg>::weak(HandleId::random::<Svg>());
+        let entity = spawn(&mut app, handle.clone());
+        app.update();
+        assert_eq!(app.world.get::<SvgState>(entity), Some(&SvgState::Loading));
+        assert!(mesh(&app, entity).is_none());
+
+        app.world.get_resource_mut::<Assets<Svg>>().unwrap().set_untracked(handle, load_test_svg(RECT));
+        app.update();
+        app.update();
+
+        assert_eq!(app.world.get::<SvgState>(entity), Some(&SvgState::Ready));
+        assert!(mesh(&app, entity).is_some());
+        assert_eq!(app.world.get_resource::<Ready>().unwrap().0, vec![entity]);
+    }
+
+    #[test]
+    fn pipeline_draws_mirrored_triangles() {
+        let mut app = app();
+        app.update();
+
+        let pipelines = app.world.get_resource::<Assets<PipelineDescriptor>>().unwrap();
+        let pipeline = pipelines.get(SVG_PIPELINE_HANDLE).unwrap();
+        assert!(pipeline.primitive.cull_mode.is_none());
+    }
+
+    #[test]
+    fn defs_only_svg_gets_no_mesh() {
+        let mut app = app();
+        let handle = add_svg(&mut app, r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <defs><rect id="square" width="10" height="10"/></defs>
+        </svg>"#);
+        let entity = spawn(&mut app, handle);
+        app.update();
+
+        assert!(mesh(&app, entity).is_none());
+        assert_eq!(app.world.get::<SvgState>(entity), Some(&SvgState::Ready));
+    }
+
+    #[test]
+    fn untouched_color_overrides_do_not_modify_the_mesh() {
+        let mut app = app();
+        app.init_resource::<ModifiedMeshes>()
+            .add_system_to_stage(CoreStage::PostUpdate, collect_modified_meshes);
+        let handle = add_svg(&mut app, TWO_RECTS);
+        let entity = spawn(&mut app, handle);
+        app.world.entity_mut(entity).insert(SvgColorOverrides {
+            by_id: vec![("right".to_string(), Color::GREEN)],
+            ..Default::default()
+        });
+        for _ in 0..3 {
+            app.update();
+        }
+        let mesh_handle = app.world.get::<Handle<Mesh>>(entity).unwrap().clone_weak();
+        app.world.get_resource_mut::<ModifiedMeshes>().unwrap().0.clear();
+
+        for _ in 0..3 {
+            app.update();
+        }
+
+        assert!(!app.world.get_resource::<ModifiedMeshes>().unwrap().0.contains(&mesh_handle));
+    }
+}
@@ -1,69 +1,187 @@
-use bevy::{math::Mat4, prelude::{Color, Component, Transform}, reflect::TypeUuid};
-use lyon_geom::euclid::default::Transform2D;
+use bevy::{math::{Mat4, Vec3}, prelude::{warn, Color, Component, Transform}, reflect::TypeUuid};
+use lyon_geom::{euclid::default::Transform2D, CubicBezierSegment, QuadraticBezierSegment};
 use lyon_svg::{parser::ViewBox, path::PathEvent};
-use lyon_tessellation::math::Point;
+use lyon_tessellation::{math::Point, FillOptions, FillRule};
+use usvg::NodeExt;
 
-use crate::Convert;
+use crate::{Convert, loader::{NodeAction, SvgError, SvgLoaderSettings}};
+
+/// Tessellation tolerance the loader uses for fills.
+pub const DEFAULT_FILL_TOLERANCE: f32 = 0.001;
+
+/// Tessellation tolerance the loader uses for strokes.
+pub const DEFAULT_STROKE_TOLERANCE: f32 = 0.01;
 
 /// A loaded and deserialized SVG file.
-#[derive(Component, Debug, TypeUuid)]
+#[derive(Clone, Component, Debug, TypeUuid)]
 #[uuid = "d2c5985d-e221-4257-9e3b-ff0fb87e28ba"]
 pub struct Svg {
-    /// The name of the file.
+    /// The name of the file, or the name passed to [`Svg::from_bytes`].
     pub name: String,
     /// Width of the SVG.
     pub width: f64,
     /// Height of the SVG.
     pub height: f64,
-    /// ViewBox of the SVG.
+    /// ViewBox of the SVG. The geometry of the paths is relative to its top left corner.
     pub view_box: ViewBox,
     /// All paths that make up the SVG
     pub paths: Vec<PathDescriptor>,
 }
 
 impl Svg {
-    pub(crate) fn from_tree(tree: usvg::Tree) -> Svg {
+    /// Creates an [`Svg`] from an already parsed [`usvg::Tree`], the same way the asset loader
+    /// does it with the default [`SvgLoaderSettings`].
+    ///
+    /// Parse the tree with [`SvgLoaderSettings::to_usvg_options`] to get the same result as the
+    /// loader, [`usvg::Options::default`] drops named groups, which [`Svg::sub_svg`] relies on.
+    ///
+    /// ```no_run
+    /// use bevy_svg::{prelude::*, usvg};
+    ///
+    /// let data = std::fs::read("assets/twinkle.svg").unwrap();
+    /// let options = SvgLoaderSettings::default().to_usvg_options();
+    /// let tree = usvg::Tree::from_data(&data, &options.to_ref()).unwrap();
+    /// let svg = Svg::from_tree(tree);
+    /// ```
+    pub fn from_tree(tree: usvg::Tree) -> Svg {
+        Svg::from_tree_with_settings(tree, &SvgLoaderSettings::default())
+    }
+
+    /// Parses an [`Svg`] from the SVG data in `bytes`, the same way the asset loader does it,
+    /// e.g. for SVGs that are generated or come from a virtual file system.
+    ///
+    /// The loader names an [`Svg`] after its file, `name` is used instead here. It shows up
+    /// in log messages, errors and [`Svg::debug_summary`].
+    pub fn from_bytes(bytes: &[u8], name: &str, settings: &SvgLoaderSettings) -> Result<Svg, SvgError> {
+        let mut svg = Svg::from_tree_with_settings(settings.parse(bytes)?, settings);
+        svg.name = name.to_string();
+
+        Ok(svg)
+    }
+
+    /// Creates an [`Svg`] from an already parsed [`usvg::Tree`], like [`Svg::from_tree`].
+    ///
+    /// Only the settings that apply after parsing are used, e.g. `dpi` has no effect here,
+    /// as it was already used by usvg while parsing the tree.
+    pub fn from_tree_with_settings(tree: usvg::Tree, settings: &SvgLoaderSettings) -> Svg {
         let view_box = tree.svg_node().view_box;
         let size = tree.svg_node().size;
         let mut descriptors = Vec::new();
+        // Nodes for which the hook returned `NodeAction::SkipSubtree`.
+        let mut skipped_subtrees = Vec::new();
 
         for node in tree.root().descendants() {
-            if let usvg::NodeKind::Path(ref p) = *node.borrow() {
-                let t = p.transform;
-                let abs_t = Transform::from_matrix(
-                    Mat4::from_cols(
-                        [t.a.abs() as f32, t.b as f32,       0.0, 0.0].into(),
-                        [t.c as f32,       t.d.abs() as f32, 0.0, 0.0].into(),
-                        [0.0,              0.0,              1.0, 0.0].into(),
-                        [t.e as f32,       t.f as f32,       0.0, 1.0].into()
-                    )
-                );
-
-                if let Some(ref fill) = p.fill {
-                    let color = match fill.paint {
-                        usvg::Paint::Color(c) =>
-                            Color::rgba_u8(c.red, c.green, c.blue, fill.opacity.to_u8()),
-                        _ => Color::default(),
-                    };
-
-                    descriptors.push(PathDescriptor {
-                        segments: p.convert().collect(),
-                        abs_transform: abs_t,
-                        color,
-                        draw_type: DrawType::Fill,
-                    });
+            // The content of `<defs>`, like clip paths, masks and patterns, is not drawn on its own.
+            if node.ancestors().any(|ancestor| matches!(*ancestor.borrow(), usvg::NodeKind::Defs)) {
+                continue;
+            }
+            if node.ancestors().skip(1).any(|ancestor| skipped_subtrees.contains(&ancestor)) {
+                continue;
+            }
+            if let Some(ref hook) = settings.node_hook {
+                match hook(&node) {
+                    NodeAction::Render => {}
+                    NodeAction::Skip => continue,
+                    NodeAction::SkipSubtree => {
+                        skipped_subtrees.push(node.clone());
+                        continue;
+                    }
                 }
+            }
+
+            match *node.borrow() {
+                usvg::NodeKind::Path(ref p) => {
+                    // Every path gets its own transform and opacity composed from its ancestors,
+                    // so nothing leaks between sibling subtrees.
+                    let mut t = node.abs_transform();
+                    t.append(&p.transform);
+                    let mut opacity = 1.0;
+                    let mut group_ids = Vec::new();
+                    for ancestor in node.ancestors().skip(1) {
+                        if let usvg::NodeKind::Group(ref g) = *ancestor.borrow() {
+                            opacity *= g.opacity.value();
+                            if !g.id.is_empty() {
+                                group_ids.push(g.id.clone());
+                            }
+                        }
+                    }
+                    group_ids.reverse();
+                    let opacity = opacity as f32;
+
+                    // The segments are relative to the path offset, so the offset is moved into the
+                    // translation. It is computed with `f64` precision and made relative to the
+                    // viewbox before the cast, so large coordinates keep their precision.
+                    let (offset_x, offset_y) = path_offset(p);
+                    let sign_x = if t.a < 0.0 { -1.0 } else { 1.0 };
+                    let sign_y = if t.d < 0.0 { -1.0 } else { 1.0 };
+                    let translation_x = t.e + t.a * offset_x + t.c * sign_y * offset_y - view_box.rect.x();
+                    let translation_y = t.f + t.b * sign_x * offset_x + t.d * offset_y - view_box.rect.y();
+                    let abs_t = Transform::from_matrix(
+                        Mat4::from_cols(
+                            [t.a.abs() as f32,      t.b as f32,            0.0, 0.0].into(),
+                            [t.c as f32,            t.d.abs() as f32,      0.0, 0.0].into(),
+                            [0.0,                   0.0,                   1.0, 0.0].into(),
+                            [translation_x as f32,  translation_y as f32,  0.0, 1.0].into()
+                        )
+                    );
+
+                    if let Some(ref fill) = p.fill {
+                        let mut color: Color = (&fill.paint, fill.opacity).convert();
+                        if settings.premultiplied_colors {
+                            color = unpremultiply(color);
+                        }
+                        color.set_a(color.a() * opacity);
 
-                if let Some(ref stroke) = p.stroke {
-                    let (color, draw_type) = stroke.convert();
+                        let fill_rule = match fill.rule {
+                            usvg::FillRule::NonZero => FillRule::NonZero,
+                            usvg::FillRule::EvenOdd => FillRule::EvenOdd,
+                        };
 
-                    descriptors.push(PathDescriptor {
-                        segments: p.convert().collect(),
-                        abs_transform: abs_t,
-                        color,
-                        draw_type,
-                    });
+                        descriptors.push(PathDescriptor {
+                            id: p.id.clone(),
+                            group_ids: group_ids.clone(),
+                            segments: (p, &t).convert().collect(),
+                            abs_transform: abs_t,
+                            color,
+                            draw_type: DrawType::Fill(
+                                FillOptions::tolerance(DEFAULT_FILL_TOLERANCE).with_fill_rule(fill_rule)
+                            ),
+                        });
+                    }
+
+                    if let Some(ref stroke) = p.stroke {
+                        let (mut color, mut draw_type) = stroke.convert();
+                        if let DrawType::Stroke(ref mut opts) = draw_type {
+                            opts.line_width = opts.line_width.max(settings.min_stroke_width);
+                            opts.tolerance = stroke_tolerance(opts.tolerance, opts.line_width);
+                        }
+                        if settings.premultiplied_colors {
+                            color = unpremultiply(color);
+                        }
+                        color.set_a(color.a() * opacity);
+
+                        descriptors.push(PathDescriptor {
+                            id: p.id.clone(),
+                            group_ids: group_ids.clone(),
+                            segments: (p, &t).convert().collect(),
+                            abs_transform: abs_t,
+                            color,
+                            draw_type,
+                        });
+                    }
+                }
+                usvg::NodeKind::Image(ref image) => {
+                    warn!("SVG node `image` with id `{}` is not supported and won't be rendered", image.id);
+                }
+                usvg::NodeKind::Group(ref g) => {
+                    if g.clip_path.is_some() || g.mask.is_some() {
+                        warn!("Clip paths and masks on SVG node `group` with id `{}` are not supported, its content is rendered unclipped", g.id);
+                    }
+                    for filter_id in &g.filter {
+                        warn_unsupported_filter(&tree, &g.id, filter_id);
+                    }
                 }
+                _ => {}
             }
         }
 
@@ -80,6 +198,236 @@ impl Svg {
             paths: descriptors,
         }
     }
+
+    /// Extracts the element with the given `id` as a standalone [`Svg`].
+    ///
+    /// Only paths that have the `id` themselves or are descendants of a group with that `id`
+    /// are kept. Size and viewbox are recomputed from the bounds of the remaining paths.
+    /// Returns `None` if no path matches.
+    pub fn sub_svg(&self, id: &str) -> Option<Svg> {
+        let paths: Vec<PathDescriptor> = self.paths.iter()
+            .filter(|path| path.has_id(id))
+            .cloned()
+            .collect();
+
+        let (min, max) = paths.iter()
+            .filter_map(PathDescriptor::bounds)
+            .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))?;
+        let size = max - min;
+
+        // The geometry is relative to the top left corner of the new viewbox.
+        let shift = Transform::from_translation(-min.extend(0.0));
+        let paths = paths.into_iter()
+            .map(|path| PathDescriptor { abs_transform: shift * path.abs_transform, ..path })
+            .collect();
+
+        Some(Svg {
+            name: format!("{}#{}", self.name, id),
+            width: size.x as f64,
+            height: size.y as f64,
+            view_box: ViewBox {
+                x: self.view_box.x + min.x as f64,
+                y: self.view_box.y + min.y as f64,
+                w: size.x as f64,
+                h: size.y as f64,
+            },
+            paths,
+        })
+    }
+
+    /// Keeps only the paths that have one of the given `ids`, or are descendants of a group with
+    /// one of them. Useful for SVGs that pack multiple visual states into one file.
+    pub fn include_ids(&mut self, ids: &[&str]) {
+        self.paths.retain(|path| ids.iter().any(|id| path.has_id(id)));
+    }
+
+    /// Removes all paths that have one of the given `ids`, or are descendants of a group with
+    /// one of them.
+    pub fn exclude_ids(&mut self, ids: &[&str]) {
+        self.paths.retain(|path| !ids.iter().any(|id| path.has_id(id)));
+    }
+
+    /// Adds a rectangle, filled with `color` and covering the whole viewbox, behind all other paths.
+    ///
+    /// Useful to debug transparency and composition of an SVG.
+    pub fn add_background(&mut self, color: Color) {
+        let min = Point::new(0.0, 0.0);
+        let max = Point::new(self.view_box.w as f32, self.view_box.h as f32);
+        let top_right = Point::new(max.x, min.y);
+        let bottom_left = Point::new(min.x, max.y);
+
+        self.paths.insert(0, PathDescriptor {
+            id: Default::default(),
+            group_ids: Vec::new(),
+            segments: vec![
+                PathEvent::Begin { at: min },
+                PathEvent::Line { from: min, to: top_right },
+                PathEvent::Line { from: top_right, to: max },
+                PathEvent::Line { from: max, to: bottom_left },
+                PathEvent::End { last: bottom_left, first: min, close: true },
+            ],
+            abs_transform: Transform::identity(),
+            color,
+            draw_type: DrawType::Fill(FillOptions::tolerance(DEFAULT_FILL_TOLERANCE)),
+        });
+    }
+
+    /// Splits every path with multiple subpaths into one [`PathDescriptor`] per subpath,
+    /// so that each of them can be addressed and colored on its own.
+    ///
+    /// Splitting changes the result of fills that rely on subpaths cutting holes into each other.
+    pub fn split_subpaths(&mut self) {
+        self.paths = self.paths.drain(..)
+            .flat_map(|path| {
+                let mut subpaths: Vec<Vec<PathEvent>> = Vec::new();
+                for event in &path.segments {
+                    match subpaths.last_mut() {
+                        Some(subpath) if !matches!(event, PathEvent::Begin { .. }) => subpath.push(*event),
+                        _ => subpaths.push(vec![*event]),
+                    }
+                }
+
+                subpaths.into_iter()
+                    .map(|segments| PathDescriptor { segments, ..path.clone() })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+    }
+
+    /// Removes all stroke paths, keeping only the fills, for a flat look.
+    pub fn fills_only(&mut self) {
+        self.paths.retain(|path| matches!(path.draw_type, DrawType::Fill(_)));
+    }
+
+    /// Removes all fill paths, keeping only the strokes, for a line-art look.
+    pub fn strokes_only(&mut self) {
+        self.paths.retain(|path| matches!(path.draw_type, DrawType::Stroke(_)));
+    }
+
+    /// Scales the geometry so that the height of the viewbox becomes `height` world units,
+    /// e.g. to place a logo in a 3D scene without guessing a `Transform` scale.
+    /// The aspect ratio is kept.
+    pub fn set_world_height(&mut self, height: f32) {
+        self.scale_uniform(height as f64 / self.view_box.h);
+    }
+
+    /// Scales the geometry so that the width of the viewbox becomes `width` world units.
+    /// See [`Svg::set_world_height`].
+    pub fn set_world_width(&mut self, width: f32) {
+        self.scale_uniform(width as f64 / self.view_box.w);
+    }
+
+    fn scale_uniform(&mut self, factor: f64) {
+        if !factor.is_finite() {
+            return;
+        }
+
+        let scale = Transform::from_scale(Vec3::new(factor as f32, factor as f32, 1.0));
+        for path in &mut self.paths {
+            path.abs_transform = scale * path.abs_transform;
+        }
+        self.width *= factor;
+        self.height *= factor;
+        self.view_box = ViewBox {
+            x: self.view_box.x * factor,
+            y: self.view_box.y * factor,
+            w: self.view_box.w * factor,
+            h: self.view_box.h * factor,
+        };
+    }
+
+    /// Changes the tessellation tolerance of all paths, a smaller tolerance gives smoother curves
+    /// at the cost of more vertices. Thin strokes keep a tolerance below their width.
+    ///
+    /// When changed through `Assets<Svg>::get_mut`, the meshes of all entities using this [`Svg`]
+    /// are rebuilt, without loading the file again.
+    pub fn set_tolerance(&mut self, tolerance: f32) {
+        for path in &mut self.paths {
+            match path.draw_type {
+                DrawType::Fill(ref mut opts) => opts.tolerance = tolerance,
+                DrawType::Stroke(ref mut opts) => opts.tolerance = stroke_tolerance(tolerance, opts.line_width),
+            }
+        }
+    }
+
+    /// Changes the tessellation tolerance of all paths to the one of the [`QualityLevel`],
+    /// see [`Svg::set_tolerance`].
+    ///
+    /// MSAA can only be set for the whole app, this allows e.g. coarse pixel art next to
+    /// smooth illustrations.
+    pub fn set_quality(&mut self, quality: QualityLevel) {
+        self.set_tolerance(quality.tolerance());
+    }
+
+    /// Changes the color of all paths drawn with the color `from` to the color `to`,
+    /// e.g. to swap the palette of an icon set.
+    ///
+    /// Only the RGB channels are compared, each may differ by one `u8` step to tolerate rounding.
+    /// The alpha of a changed path is its previous alpha multiplied by the alpha of `to`,
+    /// so opacity from the SVG is kept. Returns the number of changed paths.
+    pub fn remap_color(&mut self, from: Color, to: Color) -> usize {
+        let mut changed = 0;
+        for path in &mut self.paths {
+            if let Some(color) = remapped_color(path.color, from, to) {
+                path.color = color;
+                changed += 1;
+            }
+        }
+
+        changed
+    }
+
+    /// Multiplies the color of every path by its alpha, for custom shaders or blend setups
+    /// that expect premultiplied vertex colors. Colors are straight alpha by default.
+    ///
+    /// The multiplication is done in linear space, which the vertex colors are in.
+    /// Calling this twice premultiplies twice.
+    pub fn premultiply_alpha(&mut self) {
+        for path in &mut self.paths {
+            let [r, g, b, a] = path.color.as_linear_rgba_f32();
+            path.color = Color::rgba_linear(r * a, g * a, b * a, a);
+        }
+    }
+
+    /// Flattens every curve into `segments` lines of uniform length in curve parameter space.
+    ///
+    /// Lyon flattens curves adaptively, depending on the tolerance. After uniform flattening
+    /// the vertex count of fills no longer depends on the tolerance, so [`Svg`]s with the same
+    /// path structure produce meshes with a matching topology, e.g. for morph targets.
+    pub fn flatten_uniform(&mut self, segments: u32) {
+        let segments = segments.max(1);
+        for path in &mut self.paths {
+            path.segments = path.segments.iter()
+                .flat_map(|event| match *event {
+                    PathEvent::Quadratic { from, ctrl, to } => {
+                        let curve = QuadraticBezierSegment { from, ctrl, to };
+                        uniform_lines(from, to, segments, |t| curve.sample(t))
+                    }
+                    PathEvent::Cubic { from, ctrl1, ctrl2, to } => {
+                        let curve = CubicBezierSegment { from, ctrl1, ctrl2, to };
+                        uniform_lines(from, to, segments, |t| curve.sample(t))
+                    }
+                    event => vec![event],
+                })
+                .collect();
+        }
+    }
+
+    /// Replaces the fill and stroke of every path with an outline of the given `width` and `color`.
+    /// A path with both a fill and a stroke gets a single outline. The tessellation tolerance
+    /// of every path is kept.
+    ///
+    /// Useful for wireframe previews of complex SVGs.
+    pub fn outline(&mut self, width: f32, color: Color) {
+        self.paths.dedup_by(|path, prev| path.same_outline(prev));
+
+        for path in &mut self.paths {
+            let opt = lyon_tessellation::StrokeOptions::tolerance(path.draw_type.tolerance())
+                .with_line_width(width);
+            path.color = color;
+            path.draw_type = DrawType::Stroke(opt);
+        }
+    }
 }
 
 #[derive(Clone, Component, Copy, Debug, PartialEq)]
@@ -89,6 +437,20 @@ pub enum Origin {
     TopLeft,
     /// Center of the image or viewbox.
     Center,
+    /// Center of the top edge of the image or viewbox.
+    TopCenter,
+    /// Top right of the image or viewbox.
+    TopRight,
+    /// Center of the left edge of the image or viewbox.
+    CenterLeft,
+    /// Center of the right edge of the image or viewbox.
+    CenterRight,
+    /// Bottom left of the image or viewbox.
+    BottomLeft,
+    /// Center of the bottom edge of the image or viewbox.
+    BottomCenter,
+    /// Bottom right of the image or viewbox.
+    BottomRight,
 }
 
 impl Default for Origin {
@@ -97,20 +459,114 @@ impl Default for Origin {
     }
 }
 
-#[derive(Debug)]
+impl Origin {
+    /// Position of this origin relative to the size of the viewbox, with `(0, 0)` being the
+    /// top left and `(1, 1)` the bottom right corner. The variants follow the naming of
+    /// Bevy's sprite anchors, so an SVG and a sprite with the same anchor line up.
+    fn fraction(&self) -> (f64, f64) {
+        match self {
+            Origin::TopLeft => (0.0, 0.0),
+            Origin::Center => (0.5, 0.5),
+            Origin::TopCenter => (0.5, 0.0),
+            Origin::TopRight => (1.0, 0.0),
+            Origin::CenterLeft => (0.0, 0.5),
+            Origin::CenterRight => (1.0, 0.5),
+            Origin::BottomLeft => (0.0, 1.0),
+            Origin::BottomCenter => (0.5, 1.0),
+            Origin::BottomRight => (1.0, 1.0),
+        }
+    }
+
+    /// Translation that moves this origin of the `view_box` to `(0, 0)`, in SVG coordinates.
+    /// The geometry is already relative to the top left corner of the viewbox, so negative
+    /// origins like `viewBox="-50 -50 100 100"` are placed correctly.
+    pub(crate) fn translation(&self, view_box: &ViewBox) -> Vec3 {
+        let (x, y) = self.fraction();
+        Vec3::new(
+            -(view_box.w * x) as f32,
+            -(view_box.h * y) as f32,
+            0.0
+        )
+    }
+}
+
+/// A single drawable path of an [`Svg`].
+#[derive(Clone, Debug)]
 pub struct PathDescriptor {
+    /// The id of the path element, empty if it has none.
+    pub id: String,
+    /// The ids of all named groups enclosing this path, outermost first.
+    pub group_ids: Vec<String>,
+    /// The segments that make up the path.
     pub segments: Vec<PathEvent>,
+    /// Absolute transform of the path, relative to the top left corner of the viewbox.
     pub abs_transform: Transform,
+    /// Color the path is drawn with.
     pub color: Color,
+    /// Whether the path is filled or stroked.
     pub draw_type: DrawType,
 }
 
-#[derive(Debug)]
+impl PathDescriptor {
+    /// Whether the path itself or one of its enclosing groups has the given `id`.
+    pub fn has_id(&self, id: &str) -> bool {
+        self.id == id || self.group_ids.iter().any(|group_id| group_id == id)
+    }
+
+    /// Whether both paths have the same outline, like the fill and the stroke descriptor
+    /// of a single SVG path, which are next to each other in [`Svg::paths`].
+    pub(crate) fn same_outline(&self, other: &PathDescriptor) -> bool {
+        self.segments == other.segments && self.abs_transform == other.abs_transform
+    }
+}
+
+/// Tessellation detail of an [`Svg`], see [`Svg::set_quality`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QualityLevel {
+    /// Coarse curves with few vertices.
+    Low,
+    /// A balance between smooth curves and vertex count.
+    Medium,
+    /// Smooth curves with many vertices.
+    High,
+}
+
+impl Default for QualityLevel {
+    fn default() -> Self {
+        QualityLevel::High
+    }
+}
+
+impl QualityLevel {
+    /// The tessellation tolerance of this quality level.
+    pub fn tolerance(&self) -> f32 {
+        match self {
+            QualityLevel::Low => 0.1,
+            QualityLevel::Medium => 0.01,
+            QualityLevel::High => 0.001,
+        }
+    }
+}
+
+/// How a [`PathDescriptor`] is drawn.
+#[derive(Clone, Copy, Debug)]
 pub enum DrawType {
-    Fill,
+    /// Fill the area enclosed by the path.
+    Fill(FillOptions),
+    /// Stroke the outline of the path.
     Stroke(lyon_tessellation::StrokeOptions),
 }
 
+impl DrawType {
+    /// The tessellation tolerance of the fill or stroke.
+    pub fn tolerance(&self) -> f32 {
+        match self {
+            DrawType::Fill(opts) => opts.tolerance,
+            DrawType::Stroke(opts) => opts.tolerance,
+        }
+    }
+}
+
 // Taken from https://github.com/nical/lyon/blob/74e6b137fea70d71d3b537babae22c6652f8843e/examples/wgpu_svg/src/main.rs
 struct PathConvIter<'a> {
     iter: std::slice::Iter<'a, usvg::PathSegment>,
@@ -118,6 +574,7 @@ struct PathConvIter<'a> {
     first: Point,
     needs_end: bool,
     deferred: Option<PathEvent>,
+    offset: (f64, f64),
     scale: Transform2D<f32>,
 }
 
@@ -125,94 +582,205 @@ impl<'l> Iterator for PathConvIter<'l> {
     type Item = PathEvent;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.deferred.is_some() {
-            return self.deferred.take();
+        if let Some(event) = self.deferred.take() {
+            return Some(event.transformed(&self.scale));
         }
-        let mut return_event = None;
-        let next = self.iter.next();
-        match next {
-            Some(usvg::PathSegment::MoveTo { x, y }) => {
-                if self.needs_end {
-                    let last = self.prev;
-                    let first = self.first;
-                    self.needs_end = false;
-                    self.prev = (x, y).convert();
-                    self.deferred = Some(PathEvent::Begin { at: self.prev });
-                    self.first = self.prev;
-                    return_event = Some(PathEvent::End {
-                        last,
-                        first,
-                        close: false,
-                    });
-                } else {
-                    self.first = (x, y).convert();
-                    return_event = Some(PathEvent::Begin { at: self.first });
+
+        loop {
+            match self.iter.next() {
+                Some(usvg::PathSegment::MoveTo { x, y }) => {
+                    let at = self.point(x, y);
+                    if self.needs_end {
+                        let last = self.prev;
+                        let first = self.first;
+                        self.needs_end = false;
+                        self.prev = at;
+                        self.first = at;
+                        return Some(PathEvent::End {
+                            last,
+                            first,
+                            close: false,
+                        }.transformed(&self.scale));
+                    }
+                    // Consecutive `MoveTo`s are coalesced, a subpath only begins with its first segment.
+                    self.prev = at;
+                    self.first = at;
                 }
-            }
-            Some(usvg::PathSegment::LineTo { x, y }) => {
-                self.needs_end = true;
-                let from = self.prev;
-                self.prev = (x, y).convert();
-                return_event = Some(PathEvent::Line {
-                    from,
-                    to: self.prev,
-                });
-            }
-            Some(usvg::PathSegment::CurveTo {
-                x1,
-                y1,
-                x2,
-                y2,
-                x,
-                y,
-            }) => {
-                self.needs_end = true;
-                let from = self.prev;
-                self.prev = (x, y).convert();
-                return_event = Some(PathEvent::Cubic {
-                    from,
-                    ctrl1: (x1, y1).convert(),
-                    ctrl2: (x2, y2).convert(),
-                    to: self.prev,
-                });
-            }
-            Some(usvg::PathSegment::ClosePath) => {
-                self.needs_end = false;
-                self.prev = self.first;
-                return_event = Some(PathEvent::End {
-                    last: self.prev,
-                    first: self.first,
-                    close: true,
-                });
-            }
-            None => {
-                if self.needs_end {
-                    self.needs_end = false;
-                    let last = self.prev;
-                    let first = self.first;
-                    return_event = Some(PathEvent::End {
-                        last,
-                        first,
-                        close: false,
-                    });
+                Some(usvg::PathSegment::LineTo { x, y }) => {
+                    let from = self.prev;
+                    self.prev = self.point(x, y);
+                    return Some(self.segment(PathEvent::Line {
+                        from,
+                        to: self.prev,
+                    }));
+                }
+                Some(usvg::PathSegment::CurveTo {
+                    x1,
+                    y1,
+                    x2,
+                    y2,
+                    x,
+                    y,
+                }) => {
+                    let from = self.prev;
+                    self.prev = self.point(x, y);
+                    return Some(self.segment(PathEvent::Cubic {
+                        from,
+                        ctrl1: self.point(x1, y1),
+                        ctrl2: self.point(x2, y2),
+                        to: self.prev,
+                    }));
+                }
+                Some(usvg::PathSegment::ClosePath) => {
+                    self.prev = self.first;
+                    // Subpaths without segments are dropped.
+                    if self.needs_end {
+                        self.needs_end = false;
+                        return Some(PathEvent::End {
+                            last: self.prev,
+                            first: self.first,
+                            close: true,
+                        }.transformed(&self.scale));
+                    }
+                }
+                None => {
+                    if self.needs_end {
+                        self.needs_end = false;
+                        let last = self.prev;
+                        let first = self.first;
+                        return Some(PathEvent::End {
+                            last,
+                            first,
+                            close: false,
+                        }.transformed(&self.scale));
+                    }
+                    return None;
                 }
             }
         }
+    }
+}
+
+impl<'l> PathConvIter<'l> {
+    /// Returns the transformed `event`, preceded by a `Begin` event if it is the first segment of a subpath.
+    fn segment(&mut self, event: PathEvent) -> PathEvent {
+        if self.needs_end {
+            return event.transformed(&self.scale);
+        }
 
-        return return_event.map(|event| event.transformed(&self.scale));
+        self.needs_end = true;
+        self.deferred = Some(event);
+        PathEvent::Begin { at: self.first }.transformed(&self.scale)
     }
+
+    /// Converts a point to `f32`, relative to `offset` to keep the precision of large coordinates.
+    fn point(&self, x: &f64, y: &f64) -> Point {
+        Point::new((x - self.offset.0) as f32, (y - self.offset.1) as f32)
+    }
+}
+
+/// Warns that the filter with `filter_id` on the group with `group_id` isn't applied,
+/// and lists the primitives the filter is made of.
+fn warn_unsupported_filter(tree: &usvg::Tree, group_id: &str, filter_id: &str) {
+    warn!(
+        "Filter `{}` on SVG node `group` with id `{}` is not supported and won't be applied, its primitives are: [{}]",
+        filter_id, group_id, filter_primitives(tree, filter_id).join(", ")
+    );
+}
+
+/// The element names of the primitives the filter with `filter_id` is made of.
+fn filter_primitives(tree: &usvg::Tree, filter_id: &str) -> Vec<&'static str> {
+    let node = match tree.defs_by_id(filter_id) {
+        Some(node) => node,
+        None => return Vec::new(),
+    };
+    let primitives = match *node.borrow() {
+        usvg::NodeKind::Filter(ref filter) => filter.children.iter()
+            .map(|primitive| match primitive.kind {
+                usvg::FilterKind::FeGaussianBlur(_) => "feGaussianBlur",
+                usvg::FilterKind::FeDropShadow(_) => "feDropShadow",
+                usvg::FilterKind::FeOffset(_) => "feOffset",
+                usvg::FilterKind::FeFlood(_) => "feFlood",
+                usvg::FilterKind::FeBlend(_) => "feBlend",
+                usvg::FilterKind::FeComposite(_) => "feComposite",
+                usvg::FilterKind::FeMerge(_) => "feMerge",
+                usvg::FilterKind::FeColorMatrix(_) => "feColorMatrix",
+                _ => "other",
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    primitives
 }
 
-impl Convert<Point> for (&f64, &f64) {
-    fn convert(self) -> Point {
-        Point::new((*self.0) as f32, (*self.1) as f32)
+/// Splits a curve from `from` to `to` into `segments` lines, sampled at uniform steps of `t`.
+fn uniform_lines(from: Point, to: Point, segments: u32, sample: impl Fn(f32) -> Point) -> Vec<PathEvent> {
+    let mut lines = Vec::with_capacity(segments as usize);
+    let mut prev = from;
+    for i in 1..=segments {
+        let next = if i == segments { to } else { sample(i as f32 / segments as f32) };
+        lines.push(PathEvent::Line { from: prev, to: next });
+        prev = next;
     }
+
+    lines
 }
 
-impl<'a> Convert<PathConvIter<'a>> for &'a usvg::Path {
+/// Limits `tolerance` for a stroke of `line_width`. Thin strokes need a tolerance below their
+/// width, or they disappear or shimmer.
+fn stroke_tolerance(tolerance: f32, line_width: f32) -> f32 {
+    tolerance.min(line_width / 10.0).max(0.0001)
+}
+
+/// `color` changed to `to` if it matches `from`, following the rules of [`Svg::remap_color`].
+pub(crate) fn remapped_color(color: Color, from: Color, to: Color) -> Option<Color> {
+    const EPSILON: f32 = 1.0 / 255.0;
+    if (color.r() - from.r()).abs() <= EPSILON
+        && (color.g() - from.g()).abs() <= EPSILON
+        && (color.b() - from.b()).abs() <= EPSILON
+    {
+        Some(Color::rgba(to.r(), to.g(), to.b(), color.a() * to.a()))
+    } else {
+        None
+    }
+}
+
+/// Converts a color with premultiplied alpha into one with straight alpha.
+/// Channels are clamped to `1.0`, a fully transparent color is left untouched.
+fn unpremultiply(color: Color) -> Color {
+    let alpha = color.a();
+    if alpha <= 0.0 {
+        return color;
+    }
+
+    Color::rgba(
+        (color.r() / alpha).min(1.0),
+        (color.g() / alpha).min(1.0),
+        (color.b() / alpha).min(1.0),
+        alpha
+    )
+}
+
+/// Offset that moves the path close to `(0, 0)`, which is the position of its first `MoveTo`.
+///
+/// Large coordinates, like the ones of map exports, lose precision when they are cast
+/// to `f32`. Paths are moved by this offset first, and it is added back to their transform.
+fn path_offset(path: &usvg::Path) -> (f64, f64) {
+    path.data.iter()
+        .find_map(|segment| match *segment {
+            usvg::PathSegment::MoveTo { x, y } => Some((x, y)),
+            _ => None,
+        })
+        .unwrap_or((0.0, 0.0))
+}
+
+impl<'a> Convert<PathConvIter<'a>> for (&'a usvg::Path, &usvg::Transform) {
     fn convert(self) -> PathConvIter<'a> {
+        let (path, transform) = self;
         PathConvIter {
-            iter: self.data.iter(),
+            iter: path.data.iter(),
+            offset: path_offset(path),
             first: Point::new(0.0, 0.0),
             prev: Point::new(0.0, 0.0),
             deferred: None,
@@ -220,33 +788,46 @@ impl<'a> Convert<PathConvIter<'a>> for &'a usvg::Path {
             // For some reason the local transform of some paths has negative scale values.
             // Here we correct to positive values.
             scale: lyon_geom::Transform::scale(
-                if self.transform.a < 0.0 { -1.0 } else { 1.0 },
-                if self.transform.d < 0.0 { -1.0 } else { 1.0 }
+                if transform.a < 0.0 { -1.0 } else { 1.0 },
+                if transform.d < 0.0 { -1.0 } else { 1.0 }
             )
         }
     }
 }
 
-impl Convert<(Color, DrawType)> for &usvg::Stroke {
-    fn convert(self) -> (Color, DrawType) {
-        let color = match self.paint {
+/// Shared by fills and strokes, so both treat color and alpha the same way.
+///
+/// Known limitation: usvg 0.19 doesn't parse percentages like `fill-opacity="50%"`,
+/// they end up fully opaque.
+impl Convert<Color> for (&usvg::Paint, usvg::Opacity) {
+    fn convert(self) -> Color {
+        let (paint, opacity) = self;
+        match paint {
             usvg::Paint::Color(c) =>
-                Color::rgba_u8(c.red, c.green, c.blue, self.opacity.to_u8()),
+                Color::rgba_u8(c.red, c.green, c.blue, opacity.to_u8()),
             _ => Color::default(),
-        };
+        }
+    }
+}
+
+impl Convert<(Color, DrawType)> for &usvg::Stroke {
+    fn convert(self) -> (Color, DrawType) {
+        let color = (&self.paint, self.opacity).convert();
 
         let linecap = match self.linecap {
             usvg::LineCap::Butt => lyon_tessellation::LineCap::Butt,
             usvg::LineCap::Square => lyon_tessellation::LineCap::Square,
             usvg::LineCap::Round => lyon_tessellation::LineCap::Round,
         };
+        // usvg 0.19 only knows `miter`, `bevel` and `round`. Newer joins like `arcs` or
+        // `miter-clip` are treated as an invalid value by usvg and end up as the default `miter`.
         let linejoin = match self.linejoin {
             usvg::LineJoin::Miter => lyon_tessellation::LineJoin::Miter,
             usvg::LineJoin::Bevel => lyon_tessellation::LineJoin::Bevel,
             usvg::LineJoin::Round => lyon_tessellation::LineJoin::Round,
         };
 
-        let opt = lyon_tessellation::StrokeOptions::tolerance(0.01)
+        let opt = lyon_tessellation::StrokeOptions::tolerance(DEFAULT_STROKE_TOLERANCE)
             .with_line_width(self.width.value() as f32)
             .with_line_cap(linecap)
             .with_line_join(linejoin);
@@ -254,3 +835,480 @@ impl Convert<(Color, DrawType)> for &usvg::Stroke {
         (color, DrawType::Stroke(opt))
     }
 }
+
+/// Settings for tests, which don't need the system fonts.
+#[cfg(test)]
+pub(crate) fn test_settings() -> SvgLoaderSettings {
+    SvgLoaderSettings { load_system_fonts: false, ..Default::default() }
+}
+
+/// Loads `data` with [`test_settings`].
+#[cfg(test)]
+pub(crate) fn load_test_svg(data: &str) -> Svg {
+    Svg::from_bytes(data.as_bytes(), "test", &test_settings()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec2;
+
+    use super::*;
+
+    fn path<'a>(svg: &'a Svg, id: &str) -> &'a PathDescriptor {
+        svg.paths.iter().find(|path| path.id == id).unwrap()
+    }
+
+    /// Loads `data` with only the font shipped in `tests/fonts`, so text is laid out the same everywhere.
+    fn load_with_font(data: &str) -> Svg {
+        let settings = SvgLoaderSettings { font_family: "DejaVu Sans Mono".to_string(), ..test_settings() };
+        let mut opts = settings.to_usvg_options();
+        opts.fontdb.load_font_file(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fonts/DejaVuSansMono.ttf")).unwrap();
+        let tree = usvg::Tree::from_data(data.as_bytes(), &opts.to_ref()).unwrap();
+
+        Svg::from_tree_with_settings(tree, &settings)
+    }
+
+    /// Bounds of all paths of `svg` together.
+    fn svg_bounds(svg: &Svg) -> (Vec2, Vec2) {
+        svg.paths.iter()
+            .filter_map(PathDescriptor::bounds)
+            .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))
+            .unwrap()
+    }
+
+    fn assert_bounds(path: &PathDescriptor, min: (f32, f32), max: (f32, f32)) {
+        let (actual_min, actual_max) = path.bounds().unwrap();
+        assert!(actual_min.distance(Vec2::new(min.0, min.1)) < 1e-3, "min is {}", actual_min);
+        assert!(actual_max.distance(Vec2::new(max.0, max.1)) < 1e-3, "max is {}", actual_max);
+    }
+
+    #[test]
+    fn fill_none_has_no_fill_descriptor() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <rect width="10" height="10" fill="none" stroke="red"/>
+        </svg>"#);
+
+        assert_eq!(svg.paths.len(), 1);
+        assert!(matches!(svg.paths[0].draw_type, DrawType::Stroke(_)));
+    }
+
+    #[test]
+    fn stroke_none_has_no_stroke_descriptor() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <rect width="10" height="10" fill="red" stroke="none"/>
+        </svg>"#);
+
+        assert_eq!(svg.paths.len(), 1);
+        assert!(matches!(svg.paths[0].draw_type, DrawType::Fill(_)));
+    }
+
+    #[test]
+    fn outline_draws_fill_and_stroke_once_and_keeps_tolerance() {
+        let mut svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 10">
+            <rect width="10" height="10" fill="red" stroke="blue"/>
+            <rect x="10" width="10" height="10" fill="green"/>
+        </svg>"#);
+        svg.set_tolerance(0.5);
+        svg.outline(2.0, Color::WHITE);
+
+        assert_eq!(svg.paths.len(), 2);
+        for path in &svg.paths {
+            assert_eq!(path.color, Color::WHITE);
+            match path.draw_type {
+                DrawType::Stroke(opts) => {
+                    assert_eq!(opts.line_width, 2.0);
+                    assert_eq!(opts.tolerance, 0.5);
+                }
+                DrawType::Fill(_) => panic!("outline left a fill"),
+            }
+        }
+    }
+
+    #[test]
+    fn sibling_groups_do_not_share_transform_or_opacity() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100">
+            <g transform="translate(10 0)" opacity="0.5"><rect id="a" width="10" height="10"/></g>
+            <g transform="translate(0 20)"><rect id="b" width="10" height="10"/></g>
+        </svg>"#);
+
+        assert_bounds(path(&svg, "a"), (10.0, 0.0), (20.0, 10.0));
+        assert_bounds(path(&svg, "b"), (0.0, 20.0), (10.0, 30.0));
+        assert!((path(&svg, "a").color.a() - 0.5).abs() < 0.01);
+        assert_eq!(path(&svg, "b").color.a(), 1.0);
+    }
+
+    #[test]
+    fn sub_svg_extracts_a_named_group() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100">
+            <rect id="background" width="100" height="100"/>
+            <g id="icon">
+                <rect x="10" y="20" width="5" height="5"/>
+                <rect x="20" y="20" width="5" height="10"/>
+            </g>
+        </svg>"#);
+        let icon = svg.sub_svg("icon").unwrap();
+
+        assert_eq!(icon.paths.len(), 2);
+        assert_eq!(
+            (icon.view_box.x, icon.view_box.y, icon.view_box.w, icon.view_box.h),
+            (10.0, 20.0, 15.0, 10.0)
+        );
+        assert!(svg.sub_svg("missing").is_none());
+    }
+
+    #[test]
+    fn background_covers_the_view_box_behind_all_paths() {
+        let mut svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 10">
+            <rect width="5" height="5" fill="red"/>
+        </svg>"#);
+        svg.add_background(Color::WHITE);
+
+        assert_eq!(svg.paths.len(), 2);
+        assert_eq!(svg.paths[0].color, Color::WHITE);
+        assert!(matches!(svg.paths[0].draw_type, DrawType::Fill(_)));
+        assert_bounds(&svg.paths[0], (0.0, 0.0), (20.0, 10.0));
+    }
+
+    #[test]
+    fn sub_svg_of_an_offset_view_box_keeps_its_position() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="100 100 100 100">
+            <rect id="icon" x="110" y="120" width="5" height="5"/>
+        </svg>"#);
+        let icon = svg.sub_svg("icon").unwrap();
+
+        assert_eq!((icon.view_box.x, icon.view_box.y), (110.0, 120.0));
+        assert_bounds(&icon.paths[0], (0.0, 0.0), (5.0, 5.0));
+    }
+
+    #[test]
+    fn premultiplied_colors_are_unpremultiplied() {
+        let data = br#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <rect width="10" height="10" fill="rgb(64, 0, 0)" fill-opacity="0.5"/>
+        </svg>"#;
+        let settings = SvgLoaderSettings { premultiplied_colors: true, ..test_settings() };
+        let color = Svg::from_bytes(data, "test", &settings).unwrap().paths[0].color;
+
+        assert!((color.r() - 0.5).abs() < 0.01, "red is {}", color.r());
+        assert!((color.a() - 0.5).abs() < 0.01, "alpha is {}", color.a());
+    }
+
+    #[test]
+    fn vertical_text_is_stacked_vertically() {
+        let svg = load_with_font(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100">
+            <text x="50" y="10" font-size="10" writing-mode="tb">ABCDE</text>
+        </svg>"#);
+        let (min, max) = svg_bounds(&svg);
+
+        assert!(max.y - min.y > 2.0 * (max.x - min.x), "text spans {} to {}", min, max);
+    }
+
+    #[test]
+    fn fill_rule_is_taken_from_the_svg() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <path id="evenodd" d="M 0 0 H 10 V 10 H 0 Z M 2 2 H 8 V 8 H 2 Z" fill-rule="evenodd"/>
+            <path id="nonzero" d="M 0 0 H 10 V 10 H 0 Z"/>
+        </svg>"#);
+
+        assert!(matches!(path(&svg, "evenodd").draw_type, DrawType::Fill(opts) if opts.fill_rule == FillRule::EvenOdd));
+        assert!(matches!(path(&svg, "nonzero").draw_type, DrawType::Fill(opts) if opts.fill_rule == FillRule::NonZero));
+        assert!(svg.to_svg_string().contains(r#"fill-rule="evenodd""#));
+    }
+
+    #[test]
+    fn back_to_back_move_tos_do_not_create_empty_subpaths() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 20">
+            <path d="M 0 0 M 2 2 M 5 5 L 10 5 L 10 10 Z M 15 15"/>
+        </svg>"#);
+        let segments = &svg.paths[0].segments;
+        let begins = segments.iter().filter(|event| matches!(event, PathEvent::Begin { .. })).count();
+        let ends = segments.iter().filter(|event| matches!(event, PathEvent::End { .. })).count();
+
+        assert_eq!((begins, ends), (1, 1));
+        assert_eq!(segments.len(), 4);
+        assert_bounds(&svg.paths[0], (5.0, 5.0), (10.0, 10.0));
+    }
+
+    fn fill_alpha(opacity: &str) -> f32 {
+        load_test_svg(&format!(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <rect width="10" height="10" fill="red" fill-opacity="{}"/>
+        </svg>"#, opacity)).paths[0].color.a()
+    }
+
+    #[test]
+    fn fractional_fill_opacity_sets_the_alpha() {
+        assert!((fill_alpha("0.5") - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    #[ignore = "usvg 0.19 does not parse percentages for opacity and falls back to 1"]
+    fn percentage_fill_opacity_equals_the_fraction() {
+        assert_eq!(fill_alpha("50%"), fill_alpha("0.5"));
+    }
+
+    #[test]
+    fn remap_color_only_changes_matching_paths() {
+        let mut svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 10">
+            <rect id="red" width="10" height="10" fill="rgb(255, 0, 0)" fill-opacity="0.5"/>
+            <rect id="blue" x="10" width="10" height="10" fill="rgb(0, 0, 255)"/>
+        </svg>"#);
+
+        assert_eq!(svg.remap_color(Color::rgb(1.0, 0.0, 0.0), Color::GREEN), 1);
+        let red = path(&svg, "red").color;
+        assert_eq!((red.r(), red.g(), red.b()), (0.0, 1.0, 0.0));
+        assert!((red.a() - 0.5).abs() < 0.01);
+        assert_eq!(path(&svg, "blue").color, Color::rgb_u8(0, 0, 255));
+    }
+
+    #[test]
+    fn split_subpaths_gives_one_descriptor_per_subpath() {
+        let mut svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 30 10">
+            <path id="compound" d="M 0 0 H 5 V 5 Z M 10 0 H 15 V 5 Z M 20 0 H 25 V 5 Z"/>
+        </svg>"#);
+        assert_eq!(svg.paths.len(), 1);
+
+        svg.split_subpaths();
+
+        assert_eq!(svg.paths.len(), 3);
+        assert!(svg.paths.iter().all(|path| path.id == "compound"));
+        assert_bounds(&svg.paths[0], (0.0, 0.0), (5.0, 5.0));
+        assert_bounds(&svg.paths[2], (20.0, 0.0), (25.0, 5.0));
+    }
+
+    #[test]
+    fn tspans_keep_their_own_fill() {
+        let svg = load_with_font(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 50">
+            <text x="0" y="30" font-size="20"><tspan fill="red">A</tspan><tspan fill="blue">B</tspan></text>
+        </svg>"#);
+
+        assert!(svg.paths.iter().any(|path| path.color == Color::rgb_u8(255, 0, 0)));
+        assert!(svg.paths.iter().any(|path| path.color == Color::rgb_u8(0, 0, 255)));
+    }
+
+    const BLURRED: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+        <filter id="blur"><feGaussianBlur stdDeviation="2"/></filter>
+        <filter id="shadow"><feDropShadow dx="1" dy="1"/></filter>
+        <g id="blurred" filter="url(#blur)"><rect width="10" height="10" fill="red"/></g>
+        <g id="shadowed" filter="url(#shadow)"><rect width="10" height="10" fill="red"/></g>
+    </svg>"#;
+
+    #[test]
+    fn paths_under_an_unsupported_filter_are_still_loaded() {
+        let svg = load_test_svg(BLURRED);
+
+        assert_eq!(svg.paths.len(), 2);
+        assert!(svg.paths[0].has_id("blurred"));
+        assert!(svg.paths[1].has_id("shadowed"));
+    }
+
+    #[test]
+    fn filter_primitives_are_named_in_the_warning() {
+        let settings = test_settings();
+        let tree = settings.parse(BLURRED.as_bytes()).unwrap();
+
+        assert_eq!(filter_primitives(&tree, "blur"), vec!["feGaussianBlur"]);
+        assert_eq!(filter_primitives(&tree, "shadow"), vec!["feDropShadow"]);
+        assert!(filter_primitives(&tree, "missing").is_empty());
+    }
+
+    const THREE_RECTS: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 30 10">
+        <rect id="a" width="10" height="10" fill="red"/>
+        <rect id="b" x="10" width="10" height="10" fill="green"/>
+        <rect id="c" x="20" width="10" height="10" fill="blue"/>
+    </svg>"#;
+
+    #[test]
+    fn include_and_exclude_ids_filter_the_paths() {
+        let mut included = load_test_svg(THREE_RECTS);
+        included.include_ids(&["a"]);
+        let mut excluded = load_test_svg(THREE_RECTS);
+        excluded.exclude_ids(&["a"]);
+
+        assert_eq!(included.paths.iter().map(|path| path.id.as_str()).collect::<Vec<_>>(), vec!["a"]);
+        assert_bounds(&included.paths[0], (0.0, 0.0), (10.0, 10.0));
+        assert_eq!(excluded.paths.iter().map(|path| path.id.as_str()).collect::<Vec<_>>(), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn fill_and_stroke_of_the_same_paint_get_the_same_color() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <rect width="10" height="10" fill="rgb(51, 102, 153)" fill-opacity="0.4" stroke="rgb(51, 102, 153)" stroke-opacity="0.4"/>
+        </svg>"#);
+
+        assert_eq!(svg.paths.len(), 2);
+        assert_eq!(svg.paths[0].color, svg.paths[1].color);
+    }
+
+    #[test]
+    fn unresolved_paint_uses_the_fallback_color() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <rect width="10" height="10" fill="url(#missing) red"/>
+        </svg>"#);
+
+        assert_eq!(svg.paths.len(), 1);
+        assert_eq!(svg.paths[0].color, Color::rgb_u8(255, 0, 0));
+    }
+
+    #[test]
+    fn ungrouped_top_level_shapes_are_loaded_once_in_order() {
+        let svg = load_test_svg(THREE_RECTS);
+
+        assert_eq!(svg.paths.iter().map(|path| path.id.as_str()).collect::<Vec<_>>(), vec!["a", "b", "c"]);
+        assert_bounds(path(&svg, "b"), (10.0, 0.0), (20.0, 10.0));
+    }
+
+    #[test]
+    fn css_classes_set_the_fill() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <style>.red { fill: red }</style>
+            <rect class="red" width="10" height="10"/>
+        </svg>"#);
+
+        assert_eq!(svg.paths.len(), 1);
+        assert_eq!(svg.paths[0].color, Color::rgb_u8(255, 0, 0));
+    }
+
+    const FILL_AND_STROKE: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+        <rect width="10" height="10" fill="red" stroke="blue"/>
+    </svg>"#;
+
+    #[test]
+    fn fills_only_removes_all_strokes() {
+        let mut svg = load_test_svg(FILL_AND_STROKE);
+        svg.fills_only();
+
+        assert_eq!(svg.paths.len(), 1);
+        assert!(svg.paths.iter().all(|path| matches!(path.draw_type, DrawType::Fill(_))));
+    }
+
+    #[test]
+    fn strokes_only_removes_all_fills() {
+        let mut svg = load_test_svg(FILL_AND_STROKE);
+        svg.strokes_only();
+
+        assert_eq!(svg.paths.len(), 1);
+        assert!(svg.paths.iter().all(|path| matches!(path.draw_type, DrawType::Stroke(_))));
+    }
+
+    #[test]
+    fn node_hook_skips_nodes_and_subtrees() {
+        let data = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 40 10">
+            <rect id="a" width="10" height="10"/>
+            <rect id="b" x="10" width="10" height="10"/>
+            <g id="group">
+                <rect id="c" x="20" width="10" height="10"/>
+                <rect id="d" x="30" width="10" height="10"/>
+            </g>
+        </svg>"#;
+        let settings = SvgLoaderSettings {
+            node_hook: Some(std::sync::Arc::new(|node: &usvg::Node| match &*node.id() {
+                "b" => NodeAction::Skip,
+                "group" => NodeAction::SkipSubtree,
+                _ => NodeAction::Render,
+            })),
+            ..test_settings()
+        };
+        let svg = Svg::from_bytes(data.as_bytes(), "test", &settings).unwrap();
+
+        assert_eq!(svg.paths.iter().map(|path| path.id.as_str()).collect::<Vec<_>>(), vec!["a"]);
+    }
+
+    #[test]
+    fn from_bytes_stores_the_given_name() {
+        let settings = test_settings();
+        let svg = Svg::from_bytes(FILL_AND_STROKE.as_bytes(), "generated/badge", &settings).unwrap();
+
+        assert_eq!(svg.name, "generated/badge");
+        assert!(svg.debug_summary().starts_with("generated/badge: "));
+    }
+
+    #[test]
+    fn defs_are_not_drawn_but_used_symbols_are_extractable() {
+        let library = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <defs><rect id="square" width="10" height="10"/></defs>
+        </svg>"#);
+        assert!(library.paths.is_empty());
+
+        let used = load_test_svg(r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" viewBox="0 0 20 20">
+            <defs><symbol id="star"><rect width="5" height="5"/></symbol></defs>
+            <use id="icon" x="10" y="10" xlink:href="#star"/>
+        </svg>"##);
+        let icon = used.sub_svg("icon").unwrap();
+        assert_eq!(icon.paths.len(), 1);
+        assert_eq!((icon.view_box.x, icon.view_box.y), (10.0, 10.0));
+    }
+
+    #[test]
+    fn world_height_scales_the_geometry() {
+        let mut svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 50">
+            <rect width="100" height="50"/>
+        </svg>"#);
+        svg.set_world_height(2.0);
+
+        let (min, max) = svg_bounds(&svg);
+        assert!((max - min).distance(Vec2::new(4.0, 2.0)) < 1e-4, "size is {}", max - min);
+        assert!((svg.view_box.h - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn translated_wrapper_moves_its_path() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100">
+            <g transform="translate(30 40)">
+                <g><path d="M 0 0 H 10 V 10 H 0 Z" transform="translate(5 5)"/></g>
+            </g>
+        </svg>"#);
+
+        assert_bounds(&svg.paths[0], (35.0, 45.0), (45.0, 55.0));
+    }
+
+    #[test]
+    fn set_tolerance_keeps_hairline_strokes_fine() {
+        let mut svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <path d="M 0 5 H 10" fill="none" stroke="black" stroke-width="0.001"/>
+        </svg>"#);
+        svg.set_quality(QualityLevel::Low);
+
+        assert_eq!(svg.paths[0].draw_type.tolerance(), 0.0001);
+    }
+
+    #[test]
+    fn paths_in_clipped_and_masked_groups_are_still_loaded() {
+        let svg = load_test_svg(r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 10">
+            <clipPath id="clip"><rect width="5" height="5"/></clipPath>
+            <mask id="mask"><rect x="10" width="5" height="5" fill="white"/></mask>
+            <g clip-path="url(#clip)"><rect id="clipped" width="10" height="10" fill="red"/></g>
+            <g mask="url(#mask)"><rect id="masked" x="10" width="10" height="10" fill="blue"/></g>
+        </svg>"##);
+
+        assert_eq!(svg.paths.len(), 2);
+        assert_bounds(path(&svg, "clipped"), (0.0, 0.0), (10.0, 10.0));
+        assert_bounds(path(&svg, "masked"), (10.0, 0.0), (20.0, 10.0));
+    }
+
+    #[test]
+    fn unknown_line_joins_fall_back_to_miter() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 10">
+            <path id="arcs" d="M 0 0 L 5 5 L 10 0" fill="none" stroke="red" stroke-linejoin="arcs"/>
+            <path id="miter-clip" d="M 10 0 L 15 5 L 20 0" fill="none" stroke="red" stroke-linejoin="miter-clip"/>
+        </svg>"#);
+
+        for id in ["arcs", "miter-clip"] {
+            match path(&svg, id).draw_type {
+                DrawType::Stroke(opts) => assert_eq!(opts.line_join, lyon_tessellation::LineJoin::Miter),
+                DrawType::Fill(_) => panic!("`{}` is not a stroke", id),
+            }
+        }
+    }
+
+    #[test]
+    fn root_opacity_halves_every_alpha() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 10" opacity="0.5">
+            <rect width="10" height="10" fill="red"/>
+            <g opacity="0.5"><rect x="10" width="10" height="10" fill="blue" stroke="green"/></g>
+        </svg>"#);
+        let alphas: Vec<f32> = svg.paths.iter().map(|path| path.color.a()).collect();
+
+        assert_eq!(alphas.len(), 3);
+        assert!((alphas[0] - 0.5).abs() < 0.01, "{:?}", alphas);
+        assert!(alphas[1..].iter().all(|alpha| (alpha - 0.25).abs() < 0.01), "{:?}", alphas);
+    }
+}
@@ -1,53 +1,448 @@
-use bevy::{prelude::{error, Transform, info}, math::Vec3};
-use lyon_tessellation::{FillTessellator, StrokeTessellator, FillOptions, BuffersBuilder};
+use std::{ops::Range, path::Path};
 
-use crate::{prelude::Svg, vertex_buffer::{VertexBuffers, VertexConstructor, BufferExt}, svg::DrawType};
+use bevy::{prelude::{error, Transform, info}, math::Vec3, render::mesh::Mesh};
+use lyon_tessellation::{FillTessellator, StrokeTessellator, BuffersBuilder};
 
+use crate::{
+    Convert, loader::{SvgError, SvgLoaderSettings}, prelude::{Origin, Svg},
+    vertex_buffer::{MeshData, Vertex, VertexBuffers, VertexConstructor, BufferExt}, svg::DrawType,
+};
+
+
+/// Parses and tessellates the SVG file at `path`, without the need for a Bevy `App`.
+///
+/// Returns the geometry of every path of the SVG, in the same coordinates the [`SvgPlugin`](crate::prelude::SvgPlugin)
+/// would use for a [`SvgBundle`](crate::prelude::SvgBundle) with [`Origin::TopLeft`].
+pub fn tessellate_file<P: AsRef<Path>>(path: P, settings: &SvgLoaderSettings) -> Result<Vec<MeshData>, SvgError> {
+    let bytes = std::fs::read(path)?;
+    tessellate_bytes(&bytes, settings)
+}
+
+/// Parses and tessellates the SVG data in `bytes`, without the need for a Bevy `App`.
+///
+/// See [`tessellate_file`].
+pub fn tessellate_bytes(bytes: &[u8], settings: &SvgLoaderSettings) -> Result<Vec<MeshData>, SvgError> {
+    let svg_tree = settings.parse(bytes)?;
+    let svg = Svg::from_tree_with_settings(svg_tree, settings);
+
+    generate_path_buffers(&svg, Origin::TopLeft, &mut FillTessellator::new(), &mut StrokeTessellator::new())
+        .into_iter()
+        .map(|buffer| buffer.map(Convert::convert))
+        .collect()
+}
 
 pub(crate) fn generate_buffer(
     svg: &Svg,
+    origin: Origin,
     fill_tess: &mut FillTessellator,
     stroke_tess: &mut StrokeTessellator,
 ) -> VertexBuffers {
+    generate_ranged_buffer(svg, origin, fill_tess, stroke_tess).0
+}
+
+/// Like [`generate_buffer`], but also returns the range of vertices of every path in the buffer.
+/// The range of a path that failed to tessellate is empty.
+pub(crate) fn generate_ranged_buffer(
+    svg: &Svg,
+    origin: Origin,
+    fill_tess: &mut FillTessellator,
+    stroke_tess: &mut StrokeTessellator,
+) -> (VertexBuffers, Vec<Range<usize>>) {
     info!("Tessellating SVG: {}", svg.name);
 
-    let flip_y = Transform::from_scale(Vec3::new(1.0, -1.0, 1.0));
     let mut buffers = VertexBuffers::new();
+    let mut ranges = Vec::with_capacity(svg.paths.len());
+    for buffer in generate_path_buffers(svg, origin, fill_tess, stroke_tess) {
+        let start = buffers.vertices.len();
+        // Paths that fail to tessellate are skipped, so that the rest of the SVG is still drawn.
+        match buffer {
+            Ok(buffer) => buffers.extend_one(buffer),
+            Err(e) => error!("{}", e),
+        }
+        ranges.push(start..buffers.vertices.len());
+    }
+
+    info!("Tessellating SVG: {} ... Done", svg.name);
 
-    let mut color = None;
-    for path in &svg.paths {
+    (buffers, ranges)
+}
+
+/// Tessellates every path of the [`Svg`] into its own buffer.
+pub(crate) fn generate_path_buffers(
+    svg: &Svg,
+    origin: Origin,
+    fill_tess: &mut FillTessellator,
+    stroke_tess: &mut StrokeTessellator,
+) -> Vec<Result<VertexBuffers, SvgError>> {
+    let flip_y = Transform::from_scale(Vec3::new(1.0, -1.0, 1.0));
+    let origin = Transform::from_translation(origin.translation(&svg.view_box));
+
+    svg.paths.iter().map(|path| {
         let mut buffer = VertexBuffers::new();
 
-        if color.is_none() {
-            color = Some(path.color);
-        }
-
-        match path.draw_type {
-            DrawType::Fill => {
-                if let Err(e) = fill_tess.tessellate(
-                    path.segments.clone(),
-                    &FillOptions::tolerance(0.001),
-                    &mut BuffersBuilder::new(&mut buffer, VertexConstructor { color: path.color })
-                ) {
-                    error!("FillTessellator error: {:?}", e)
-                }
-            },
-            DrawType::Stroke(opts) => {
-                if let Err(e) = stroke_tess.tessellate(
-                    path.segments.clone(),
-                    &opts,
-                    &mut BuffersBuilder::new(&mut buffer, VertexConstructor { color: path.color })
-                ) {
-                    error!("StrokeTessellator error: {:?}", e)
-                }
-            }
+        let result = match path.draw_type {
+            DrawType::Fill(opts) => fill_tess.tessellate(
+                path.segments.clone(),
+                &opts,
+                &mut BuffersBuilder::new(&mut buffer, VertexConstructor { color: path.color })
+            ),
+            DrawType::Stroke(opts) => stroke_tess.tessellate(
+                path.segments.clone(),
+                &opts,
+                &mut BuffersBuilder::new(&mut buffer, VertexConstructor { color: path.color })
+            ),
+        };
+        if let Err(e) = result {
+            return Err(SvgError::Tessellation {
+                svg: svg.name.clone(),
+                path: path.id.clone(),
+                error: format!("{:?}", e),
+            });
         }
 
         // Bevy has a different y-axis origin, so we need to flip that axis
-        buffer.apply_transform(flip_y * path.abs_transform);
-        buffers.extend_one(buffer);
+        buffer.apply_transform(flip_y * origin * path.abs_transform);
+        // Keep NaN out of the mesh, it doesn't show up as an error but as glitches on the GPU.
+        if !buffer.vertices.iter().all(Vertex::is_finite) {
+            return Err(SvgError::NonFinite {
+                svg: svg.name.clone(),
+                path: path.id.clone(),
+            });
+        }
+        Ok(buffer)
+    }).collect()
+}
+
+impl Svg {
+    /// Tessellates every path at the given `tolerance` and returns its raw geometry, as indexed
+    /// triangle lists, before all paths are merged into one Bevy `Mesh`.
+    ///
+    /// Useful for custom rendering or further processing of the geometry.
+    pub fn vertex_buffers(&self, tolerance: f32) -> Result<Vec<MeshData>, SvgError> {
+        let mut svg = self.clone();
+        svg.set_tolerance(tolerance);
+
+        generate_path_buffers(&svg, Origin::TopLeft, &mut FillTessellator::new(), &mut StrokeTessellator::new())
+            .into_iter()
+            .map(|buffer| buffer.map(Convert::convert))
+            .collect()
     }
-    info!("Tessellating SVG: {} ... Done", svg.name);
 
-    buffers
+    /// Interpolates between the meshes of this and the `other` [`Svg`] at `t`, from `0.0` for
+    /// this one to `1.0` for `other`, e.g. to morph one icon into another.
+    ///
+    /// Both need the same topology: the same number of paths, and every pair of paths has to
+    /// tessellate into the same number of vertices with the same indices. Flattening both with
+    /// [`Svg::flatten_uniform`] helps to get there. Returns one mesh per path, or `None` if the
+    /// topology doesn't match or a path fails to tessellate.
+    pub fn morph(&self, other: &Svg, t: f32) -> Option<Vec<Mesh>> {
+        if self.paths.len() != other.paths.len() {
+            return None;
+        }
+
+        let mut fill_tess = FillTessellator::new();
+        let mut stroke_tess = StrokeTessellator::new();
+        let from = generate_path_buffers(self, Origin::TopLeft, &mut fill_tess, &mut stroke_tess);
+        let to = generate_path_buffers(other, Origin::TopLeft, &mut fill_tess, &mut stroke_tess);
+
+        from.into_iter()
+            .zip(to)
+            .map(|(from, to)| from.ok()?.lerp(&to.ok()?, t).map(Convert::convert))
+            .collect()
+    }
+
+    /// Coarsens the tessellation tolerance until this [`Svg`] tessellates into at most
+    /// `max_triangles` triangles. A safety valve for untrusted or overly complex SVGs.
+    ///
+    /// The tolerance is doubled up to 16 times. Returns the number of triangles at the final
+    /// tolerance, which can still exceed `max_triangles` if the straight parts alone need more.
+    pub fn limit_triangles(&mut self, max_triangles: usize) -> usize {
+        const MAX_COARSENINGS: u32 = 16;
+        let mut fill_tess = FillTessellator::new();
+        let mut stroke_tess = StrokeTessellator::new();
+        let mut count_triangles = |svg: &Svg| {
+            generate_path_buffers(svg, Origin::TopLeft, &mut fill_tess, &mut stroke_tess)
+                .into_iter()
+                .filter_map(Result::ok)
+                .map(|buffer| buffer.indices.len() / 3)
+                .sum::<usize>()
+        };
+
+        let mut tolerance = self.paths.iter()
+            .map(|path| path.draw_type.tolerance())
+            .fold(f32::INFINITY, f32::min);
+        let mut triangles = count_triangles(self);
+        let mut coarsenings = 0;
+        while triangles > max_triangles && coarsenings < MAX_COARSENINGS {
+            tolerance *= 2.0;
+            self.set_tolerance(tolerance);
+            triangles = count_triangles(self);
+            coarsenings += 1;
+        }
+
+        triangles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::svg::{load_test_svg, test_settings, QualityLevel};
+
+    /// Positions of all vertices of the merged mesh of `svg`, placed with `origin`.
+    fn positions(svg: &Svg, origin: Origin) -> Vec<[f32; 3]> {
+        let buffer = generate_buffer(svg, origin, &mut FillTessellator::new(), &mut StrokeTessellator::new());
+        let mesh: MeshData = buffer.convert();
+        mesh.positions
+    }
+
+    /// Min and max corner of `positions` in x and y.
+    fn bounds(positions: &[[f32; 3]]) -> ([f32; 2], [f32; 2]) {
+        positions.iter().fold(
+            ([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]),
+            |(min, max), p| ([min[0].min(p[0]), min[1].min(p[1])], [max[0].max(p[0]), max[1].max(p[1])])
+        )
+    }
+
+    fn assert_close(actual: [f32; 2], expected: [f32; 2], epsilon: f32) {
+        assert!(
+            (actual[0] - expected[0]).abs() < epsilon && (actual[1] - expected[1]).abs() < epsilon,
+            "{:?} is not close to {:?}", actual, expected
+        );
+    }
+
+    #[test]
+    fn negative_view_box_origin_is_centered() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-50 -50 100 100">
+            <rect x="-10" y="-10" width="20" height="20"/>
+        </svg>"#);
+        let (min, max) = bounds(&positions(&svg, Origin::Center));
+
+        assert_close(min, [-10.0, -10.0], 1e-4);
+        assert_close(max, [10.0, 10.0], 1e-4);
+    }
+
+    #[test]
+    fn tessellate_file_produces_geometry() {
+        let settings = test_settings();
+        let meshes = tessellate_file("assets/twinkle.svg", &settings).unwrap();
+
+        assert!(meshes.iter().any(|mesh| !mesh.indices.is_empty()));
+        for mesh in &meshes {
+            assert_eq!(mesh.positions.len(), mesh.colors.len());
+            assert_eq!(mesh.indices.len() % 3, 0);
+            assert!(mesh.indices.iter().all(|&index| (index as usize) < mesh.positions.len()));
+        }
+    }
+
+    #[test]
+    fn coordinates_near_a_million_keep_their_precision() {
+        let settings = test_settings();
+        let meshes = tessellate_bytes(br#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="1000000 1000000 100 100">
+            <path d="M 1000010.3 1000010.3 L 1000090.3 1000010.3 L 1000050.3 1000090.7 Z"/>
+        </svg>"#, &settings).unwrap();
+        let (min, max) = bounds(&meshes[0].positions);
+
+        assert_close(min, [10.3, -90.7], 1e-3);
+        assert_close(max, [90.3, -10.3], 1e-3);
+    }
+
+    #[test]
+    fn finer_tolerance_gives_more_vertices_on_curves() {
+        let mut svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100">
+            <circle cx="50" cy="50" r="50"/>
+        </svg>"#);
+        svg.set_tolerance(1.0);
+        let coarse = positions(&svg, Origin::TopLeft).len();
+        svg.set_tolerance(0.01);
+        let fine = positions(&svg, Origin::TopLeft).len();
+
+        assert!(fine > coarse, "{} vertices at 0.01, {} at 1.0", fine, coarse);
+    }
+
+    #[test]
+    fn uniform_flattening_does_not_depend_on_the_tolerance() {
+        let mut svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100">
+            <circle cx="50" cy="50" r="50"/>
+        </svg>"#);
+        svg.flatten_uniform(8);
+        svg.set_tolerance(1.0);
+        let coarse = positions(&svg, Origin::TopLeft).len();
+        svg.set_tolerance(0.01);
+        let fine = positions(&svg, Origin::TopLeft).len();
+
+        assert_eq!(coarse, fine);
+    }
+
+    #[test]
+    fn limit_triangles_coarsens_until_the_cap_fits() {
+        let mut svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100">
+            <circle cx="50" cy="50" r="50"/>
+        </svg>"#);
+        let triangles = |svg: &Svg| generate_buffer(svg, Origin::TopLeft, &mut FillTessellator::new(), &mut StrokeTessellator::new()).indices.len() / 3;
+        assert!(triangles(&svg) > 20);
+
+        let limited = svg.limit_triangles(20);
+
+        assert!(limited <= 20, "{} triangles", limited);
+        assert_eq!(limited, triangles(&svg));
+    }
+
+    #[test]
+    fn origins_match_the_sprite_anchors() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 50">
+            <rect width="100" height="50"/>
+        </svg>"#);
+        let origins = [
+            (Origin::TopLeft, [0.0, -50.0], [100.0, 0.0]),
+            (Origin::TopCenter, [-50.0, -50.0], [50.0, 0.0]),
+            (Origin::TopRight, [-100.0, -50.0], [0.0, 0.0]),
+            (Origin::CenterLeft, [0.0, -25.0], [100.0, 25.0]),
+            (Origin::Center, [-50.0, -25.0], [50.0, 25.0]),
+            (Origin::CenterRight, [-100.0, -25.0], [0.0, 25.0]),
+            (Origin::BottomLeft, [0.0, 0.0], [100.0, 50.0]),
+            (Origin::BottomCenter, [-50.0, 0.0], [50.0, 50.0]),
+            (Origin::BottomRight, [-100.0, 0.0], [0.0, 50.0]),
+        ];
+
+        for (origin, expected_min, expected_max) in origins {
+            let (min, max) = bounds(&positions(&svg, origin));
+            assert_close(min, expected_min, 1e-4);
+            assert_close(max, expected_max, 1e-4);
+        }
+    }
+
+    #[test]
+    fn hairlines_are_widened_to_the_minimum_stroke_width() {
+        let data = br#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <path d="M 0 5 H 10" fill="none" stroke="black" stroke-width="0.001"/>
+        </svg>"#;
+        let settings = SvgLoaderSettings { min_stroke_width: 0.5, ..test_settings() };
+        let svg = Svg::from_bytes(data, "test", &settings).unwrap();
+        let (min, max) = bounds(&positions(&svg, Origin::TopLeft));
+
+        assert!(!positions(&svg, Origin::TopLeft).is_empty());
+        assert_close(min, [0.0, -5.25], 1e-3);
+        assert_close(max, [10.0, -4.75], 1e-3);
+    }
+
+    #[test]
+    fn tessellation_error_names_the_svg_and_path() {
+        let error = SvgError::Tessellation {
+            svg: "icons.svg".to_string(),
+            path: "broken".to_string(),
+            error: "Internal".to_string(),
+        };
+
+        assert_eq!(error.to_string(), "failed to tessellate path `broken` of SVG `icons.svg`: Internal");
+    }
+
+    #[test]
+    fn vertex_buffers_of_a_square_are_consistent() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <rect width="10" height="10"/>
+        </svg>"#);
+        let buffers = svg.vertex_buffers(0.1).unwrap();
+
+        assert_eq!(buffers.len(), 1);
+        assert_eq!(buffers[0].positions.len(), 4);
+        assert_eq!(buffers[0].colors.len(), 4);
+        assert_eq!(buffers[0].indices.len(), 6);
+        assert!(buffers[0].indices.iter().all(|&index| index < 4));
+    }
+
+    fn triangle(size: f32) -> Svg {
+        load_test_svg(&format!(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 20">
+            <path d="M 0 0 L {size} 0 L 0 {size} Z"/>
+        </svg>"#, size = size))
+    }
+
+    #[test]
+    fn morph_interpolates_matching_topologies() {
+        let meshes = triangle(10.0).morph(&triangle(20.0), 0.5).unwrap();
+
+        assert_eq!(meshes.len(), 1);
+        let positions: Vec<[f32; 3]> = meshes[0].attribute(Mesh::ATTRIBUTE_POSITION).unwrap()
+            .get_bytes()
+            .chunks(12)
+            .map(|vertex| {
+                let component = |i: usize| f32::from_ne_bytes(vertex[4 * i..4 * i + 4].try_into().unwrap());
+                [component(0), component(1), component(2)]
+            })
+            .collect();
+        let (min, max) = bounds(&positions);
+        assert_eq!(positions.len(), 3);
+        assert_close(min, [0.0, -15.0], 1e-4);
+        assert_close(max, [15.0, 0.0], 1e-4);
+
+        let square = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 20">
+            <rect width="10" height="10"/>
+        </svg>"#);
+        assert!(triangle(10.0).morph(&square, 0.5).is_none());
+    }
+
+    #[test]
+    fn missing_view_box_is_taken_from_the_size() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" width="40" height="20">
+            <rect x="10" y="5" width="10" height="5"/>
+        </svg>"#);
+        let (min, max) = bounds(&positions(&svg, Origin::Center));
+
+        assert_eq!((svg.view_box.x, svg.view_box.y, svg.view_box.w, svg.view_box.h), (0.0, 0.0, 40.0, 20.0));
+        assert_close(min, [-10.0, 0.0], 1e-4);
+        assert_close(max, [0.0, 5.0], 1e-4);
+    }
+
+    #[test]
+    fn center_uses_the_view_box_when_the_size_differs() {
+        let svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="200" viewBox="0 0 100 100">
+            <rect x="40" y="40" width="20" height="20"/>
+        </svg>"#);
+        let (min, max) = bounds(&positions(&svg, Origin::Center));
+
+        assert_eq!((svg.width, svg.view_box.w), (200.0, 100.0));
+        assert_close(min, [-10.0, -10.0], 1e-4);
+        assert_close(max, [10.0, 10.0], 1e-4);
+    }
+
+    #[test]
+    fn degenerate_transform_is_caught_before_the_mesh() {
+        let mut svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <rect width="10" height="10"/>
+        </svg>"#);
+        svg.paths[0].abs_transform = Transform::from_scale(Vec3::new(f32::INFINITY, 0.0, 1.0));
+        let results = generate_path_buffers(&svg, Origin::TopLeft, &mut FillTessellator::new(), &mut StrokeTessellator::new());
+
+        assert!(matches!(results[0], Err(SvgError::NonFinite { .. })));
+        assert!(positions(&svg, Origin::TopLeft).is_empty());
+    }
+
+    #[test]
+    fn premultiplied_vertex_colors() {
+        let mut svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+            <rect width="10" height="10" fill="red" fill-opacity="0.5"/>
+        </svg>"#);
+        svg.premultiply_alpha();
+        let buffer = generate_buffer(&svg, Origin::TopLeft, &mut FillTessellator::new(), &mut StrokeTessellator::new());
+        let mesh: MeshData = buffer.convert();
+
+        assert!(!mesh.colors.is_empty());
+        for [r, g, b, a] in mesh.colors {
+            assert!((r - 0.5).abs() < 0.01 && g == 0.0 && b == 0.0 && (a - 0.5).abs() < 0.01, "{:?}", [r, g, b, a]);
+        }
+    }
+
+    #[test]
+    fn higher_quality_gives_finer_tessellation() {
+        let mut svg = load_test_svg(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100">
+            <circle cx="50" cy="50" r="50"/>
+        </svg>"#);
+        let mut vertices = Vec::new();
+        for quality in [QualityLevel::Low, QualityLevel::Medium, QualityLevel::High] {
+            svg.set_quality(quality);
+            vertices.push(positions(&svg, Origin::TopLeft).len());
+        }
+
+        assert!(vertices[0] < vertices[1] && vertices[1] < vertices[2], "{:?}", vertices);
+    }
 }
@@ -16,6 +16,13 @@ pub(crate) struct Vertex {
     color: [f32; 4],
 }
 
+impl Vertex {
+    /// Whether the position and color of this vertex are neither NaN nor infinite.
+    pub(crate) fn is_finite(&self) -> bool {
+        self.position.iter().chain(&self.color).all(|v| v.is_finite())
+    }
+}
+
 /// The index type of a Bevy [`Mesh`](bevy::render::mesh::Mesh).
 pub(crate) type IndexType = u32;
 
@@ -47,6 +54,27 @@ impl Convert<Mesh> for VertexBuffers {
     }
 }
 
+/// Raw geometry of a tessellated path, independent of Bevy's [`Mesh`](bevy::render::mesh::Mesh).
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    /// Position of each vertex.
+    pub positions: Vec<[f32; 3]>,
+    /// Linear RGBA color of each vertex.
+    pub colors: Vec<[f32; 4]>,
+    /// Indices into `positions` and `colors`, three per triangle.
+    pub indices: Vec<u32>,
+}
+
+impl Convert<MeshData> for VertexBuffers {
+    fn convert(self) -> MeshData {
+        MeshData {
+            positions: self.vertices.iter().map(|v| v.position).collect(),
+            colors: self.vertices.iter().map(|v| v.color).collect(),
+            indices: self.indices,
+        }
+    }
+}
+
 /// Zero-sized type used to implement various vertex construction traits from Lyon.
 pub(crate) struct VertexConstructor {
     pub(crate) color: Color,
@@ -76,6 +104,7 @@ pub(crate) trait BufferExt<A> {
     fn apply_transform(&mut self, transform: Transform);
     fn extend_one(&mut self, item: A);
     fn extend<T: IntoIterator<Item = A>>(&mut self, iter: T);
+    fn lerp(&self, other: &A, t: f32) -> Option<A>;
 }
 
 impl BufferExt<VertexBuffers> for VertexBuffers {
@@ -110,4 +139,23 @@ impl BufferExt<VertexBuffers> for VertexBuffers {
             offset += buf.vertices.len() as u32;
         }
     }
+
+    /// Interpolates positions and colors towards `other`, which needs the same topology.
+    fn lerp(&self, other: &VertexBuffers, t: f32) -> Option<VertexBuffers> {
+        if self.vertices.len() != other.vertices.len() || self.indices != other.indices {
+            return None;
+        }
+
+        let mut buffers = self.clone();
+        for (vertex, other) in buffers.vertices.iter_mut().zip(&other.vertices) {
+            for (a, b) in vertex.position.iter_mut().zip(&other.position) {
+                *a += (b - *a) * t;
+            }
+            for (a, b) in vertex.color.iter_mut().zip(&other.color) {
+                *a += (b - *a) * t;
+            }
+        }
+
+        Some(buffers)
+    }
 }